@@ -0,0 +1,223 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Line {
+    Same,
+    Removed,
+    Added,
+}
+
+/// the furthest `x` reached on each diagonal `k` of the edit graph, where a point `(x, y)` is on
+/// diagonal `x - y`, and `x` counts the lines of `a` consumed
+struct Diagonals {
+    x: Vec<usize>,
+    /// the index of diagonal 0
+    offset: isize,
+}
+
+impl Diagonals {
+    /// enough diagonals for diffing any lines that are `len` lines long in total
+    fn new(len: usize) -> Self {
+        let offset = len as isize / 2 + 2;
+        Self {
+            x: vec![0; 2 * offset as usize + 1],
+            offset,
+        }
+    }
+}
+impl std::ops::Index<isize> for Diagonals {
+    type Output = usize;
+
+    fn index(&self, k: isize) -> &usize {
+        &self.x[(k + self.offset) as usize]
+    }
+}
+impl std::ops::IndexMut<isize> for Diagonals {
+    fn index_mut(&mut self, k: isize) -> &mut usize {
+        &mut self.x[(k + self.offset) as usize]
+    }
+}
+
+fn common_prefix(a: &[&str], b: &[&str]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+fn common_suffix(a: &[&str], b: &[&str]) -> usize {
+    a.iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count()
+}
+
+/// appends a shortest edit script turning `a` into `b` to `lines`, using Myers' linear space
+/// algorithm: the middle of the script is found, and the parts before and after it are
+/// diffed on their own
+fn diff(a: &[&str], b: &[&str], lines: &mut Vec<Line>, diagonals: &mut (Diagonals, Diagonals)) {
+    let prefix = common_prefix(a, b);
+    let suffix = common_suffix(&a[prefix..], &b[prefix..]);
+    let (a, b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    lines.resize(lines.len() + prefix, Line::Same);
+    if a.is_empty() {
+        lines.resize(lines.len() + b.len(), Line::Added);
+    } else if b.is_empty() {
+        lines.resize(lines.len() + a.len(), Line::Removed);
+    } else {
+        let (x, y) = middle_snake(a, b, diagonals);
+        diff(&a[..x], &b[..y], lines, diagonals);
+        diff(&a[x..], &b[y..], lines, diagonals);
+    }
+    lines.resize(lines.len() + suffix, Line::Same);
+}
+
+/// finds a point on a shortest edit path from the start of `a` and `b` to their ends, by
+/// searching forward from the start and backward from the end until the two searches meet.
+/// neither may be empty, and they can't start or end with the same line, so the point is
+/// never at either end
+fn middle_snake(
+    a: &[&str],
+    b: &[&str],
+    (forward, backward): &mut (Diagonals, Diagonals),
+) -> (usize, usize) {
+    let (n, m) = (a.len(), b.len());
+    // the diagonal the end is on, which is where the backward search starts
+    let delta = n as isize - m as isize;
+    forward[1] = 0;
+    backward[1] = 0;
+    for d in 0..=(n + m).div_ceil(2) as isize {
+        for k in (-d..=d).step_by(2) {
+            // move down from the diagonal above, or right from the one below
+            let start = if k == -d || k != d && forward[k - 1] < forward[k + 1] {
+                forward[k + 1]
+            } else {
+                forward[k - 1] + 1
+            };
+            let y = (start as isize - k) as usize;
+            let mut x = start;
+            if x < n && y < m {
+                x += common_prefix(&a[x..], &b[y..]);
+            }
+            forward[k] = x;
+            // with an odd `delta`, the paths can only meet after a forward step
+            let opposite = delta - k;
+            if delta % 2 != 0 && opposite.abs() < d && x + backward[opposite] >= n {
+                return (start, y);
+            }
+        }
+        // the same search, with `x` and `y` counting lines from the ends
+        for k in (-d..=d).step_by(2) {
+            let start = if k == -d || k != d && backward[k - 1] < backward[k + 1] {
+                backward[k + 1]
+            } else {
+                backward[k - 1] + 1
+            };
+            let mut y = (start as isize - k) as usize;
+            let mut x = start;
+            if x < n && y < m {
+                let common = common_suffix(&a[..n - x], &b[..m - y]);
+                x += common;
+                y += common;
+            }
+            backward[k] = x;
+            let opposite = delta - k;
+            if delta % 2 == 0 && opposite.abs() <= d && x + forward[opposite] >= n {
+                return (n - x, m - y);
+            }
+        }
+    }
+    unreachable!("the searches meet by the time they've made every edit")
+}
+
+/// the `start,count` of a hunk header, where a count of 1 is left out
+fn hunk_range(start: usize, count: usize) -> String {
+    // an empty range starts at the line before it
+    let start = start + (count > 0) as usize;
+    match count {
+        1 => format!("{start}"),
+        _ => format!("{start},{count}"),
+    }
+}
+
+/// adds `line` after `prefix`, marking the last line of a file without a newline like `diff`
+fn push_line(out: &mut String, prefix: char, line: &str) {
+    out.push(prefix);
+    *out += line;
+    if !line.ends_with('\n') {
+        *out += "\n\\ No newline at end of file\n";
+    }
+}
+
+/// a line diff of `old` and `new` in the unified format of `diff -u`, without the file names,
+/// with `context` unchanged lines around each change
+pub fn unified(old: &str, new: &str, context: usize) -> String {
+    let old: Vec<_> = old.split_inclusive('\n').collect();
+    let new: Vec<_> = new.split_inclusive('\n').collect();
+
+    let mut lines = Vec::with_capacity(old.len().max(new.len()));
+    let len = old.len() + new.len();
+    let mut diagonals = (Diagonals::new(len), Diagonals::new(len));
+    diff(&old, &new, &mut lines, &mut diagonals);
+
+    // the lines removed by each change come before the ones added in their place
+    for change in lines.split_mut(|l| *l == Line::Same) {
+        change.sort_by_key(|l| *l == Line::Added);
+    }
+
+    let mut out = String::new();
+    let (mut old_line, mut new_line) = (0, 0);
+    let mut k = 0;
+    while k < lines.len() {
+        if lines[k] == Line::Same {
+            old_line += 1;
+            new_line += 1;
+            k += 1;
+            continue;
+        }
+        // a hunk runs until there are more than twice `context` unchanged lines in a row
+        let start = k.saturating_sub(context);
+        let mut end = k;
+        let mut same = 0;
+        while end < lines.len() && same <= 2 * context {
+            same = if lines[end] == Line::Same {
+                same + 1
+            } else {
+                0
+            };
+            end += 1;
+        }
+        end -= same.saturating_sub(context);
+
+        let before = k - start;
+        let count = |kind| lines[start..end].iter().filter(|l| **l != kind).count();
+        let old_start = old_line - before;
+        let new_start = new_line - before;
+        out += &format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, count(Line::Added)),
+            hunk_range(new_start, count(Line::Removed)),
+        );
+        let (mut o, mut n) = (old_start, new_start);
+        for line in &lines[start..end] {
+            match line {
+                Line::Same => {
+                    push_line(&mut out, ' ', old[o]);
+                    o += 1;
+                    n += 1;
+                }
+                Line::Removed => {
+                    push_line(&mut out, '-', old[o]);
+                    o += 1;
+                }
+                Line::Added => {
+                    push_line(&mut out, '+', new[n]);
+                    n += 1;
+                }
+            }
+        }
+        old_line = o;
+        new_line = n;
+        k = end;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests;
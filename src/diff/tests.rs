@@ -0,0 +1,119 @@
+use super::unified;
+
+fn check(cases: &[(&str, &str, usize, &str)]) {
+    for (old, new, context, diff) in cases {
+        assert_eq!(
+            unified(old, new, *context),
+            *diff,
+            "{old:?} -> {new:?} with {context} lines of context"
+        );
+    }
+}
+
+#[test]
+fn empty_inputs() {
+    check(&[
+        ("", "", 3, ""),
+        ("a\nb\n", "a\nb\n", 3, ""),
+        ("", "a\nb\n", 3, "@@ -0,0 +1,2 @@\n+a\n+b\n"),
+        ("a\nb\n", "", 3, "@@ -1,2 +0,0 @@\n-a\n-b\n"),
+        ("", "a\n", 3, "@@ -0,0 +1 @@\n+a\n"),
+    ]);
+}
+
+#[test]
+fn odd_and_even_deltas() {
+    check(&[
+        // the lines between the common prefix and suffix differ in length by one
+        (
+            "a\nb\nc\nd\ne\n",
+            "a\nx\ny\nc\nd\ne\n",
+            1,
+            "@@ -1,3 +1,4 @@\n a\n-b\n+x\n+y\n c\n",
+        ),
+        (
+            "a\nb\nc\nd\ne\n",
+            "a\nx\nd\ne\n",
+            1,
+            "@@ -1,4 +1,3 @@\n a\n-b\n-c\n+x\n d\n",
+        ),
+        (
+            "b\nc\n",
+            "a\nb\nc\nd\ne\n",
+            1,
+            "@@ -1,2 +1,5 @@\n+a\n b\n c\n+d\n+e\n",
+        ),
+        (
+            "x\na\nb\nc\ny\nz\n",
+            "a\nb\nc\n",
+            1,
+            "@@ -1,2 +1 @@\n-x\n a\n@@ -4,3 +3 @@\n c\n-y\n-z\n",
+        ),
+        // by zero or two
+        (
+            "1\n2\n3\n4\n5\n6\n7\n8\n9\n",
+            "1\n2\n3\n4\nfive\n6\n7\n8\n9\n",
+            2,
+            "@@ -3,5 +3,5 @@\n 3\n 4\n-5\n+five\n 6\n 7\n",
+        ),
+        (
+            "a\nb\nc\nd\n",
+            "b\nc\n",
+            1,
+            "@@ -1,4 +1,2 @@\n-a\n b\n c\n-d\n",
+        ),
+        (
+            "b\nc\n",
+            "a\nb\nc\nd\n",
+            1,
+            "@@ -1,2 +1,4 @@\n+a\n b\n c\n+d\n",
+        ),
+    ]);
+}
+
+#[test]
+fn hunks_merge_and_trim_context() {
+    let old = "a\nb\nc\nd\ne\nf\ng\nh\n";
+    let new = "a\nB\nc\nd\ne\nf\nG\nh\n";
+    check(&[
+        // the four lines between the changes are within twice the context
+        (
+            old,
+            new,
+            2,
+            "@@ -1,8 +1,8 @@\n a\n-b\n+B\n c\n d\n e\n f\n-g\n+G\n h\n",
+        ),
+        (
+            old,
+            new,
+            1,
+            "@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n@@ -6,3 +6,3 @@\n f\n-g\n+G\n h\n",
+        ),
+        (old, new, 0, "@@ -2 +2 @@\n-b\n+B\n@@ -7 +7 @@\n-g\n+G\n"),
+        // five lines between them are one too many
+        (
+            "a\nb\nc\nd\ne\nf\ng\nh\ni\n",
+            "a\nB\nc\nd\ne\nf\ng\nH\ni\n",
+            2,
+            "@@ -1,4 +1,4 @@\n a\n-b\n+B\n c\n d\n@@ -6,4 +6,4 @@\n f\n g\n-h\n+H\n i\n",
+        ),
+    ]);
+}
+
+#[test]
+fn missing_final_newlines() {
+    check(&[
+        (
+            "a\nb",
+            "a\nc",
+            3,
+            "@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+c\n\\ No newline at end of file\n",
+        ),
+        (
+            "a\nb",
+            "a\nb\n",
+            3,
+            "@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+b\n",
+        ),
+    ]);
+}
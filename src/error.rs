@@ -13,6 +13,7 @@ macro_rules! make_error {
                 ];
                 $variant:ident {
                     $(
+                        $(#[$field_meta:meta])*
                         $field:ident: $typ:ty
                     ),* $(,)?
                 } $(,)?
@@ -23,13 +24,14 @@ macro_rules! make_error {
         #[derive(Debug, Clone)]
         pub enum $struct_name {
             $(
-                $variant { $($field: $typ),* },
+                $variant { $($(#[$field_meta])* $field: $typ),* },
             )*
         }
 
         impl $struct_name {
             pub fn into_report(self) -> crate::error::Report {
 
+                #[allow(unused_imports)]
                 use owo_colors::OwoColorize;
                 match self {
                     $(
@@ -37,9 +39,9 @@ macro_rules! make_error {
                             title: String::from($title),
                             typ: crate::error::ReportType::$report_type,
                             messages: Box::new([$(
-                                ($area, format!($fmt $( , $( ($args).bright_white() )* )? )),
+                                ($area, format!($fmt $( , $( ($args).bright_white() ),* )? )),
                             )*])
-                        }
+                        },
                     )*
                 }
             }
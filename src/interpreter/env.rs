@@ -0,0 +1,237 @@
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    rc::Rc,
+};
+
+use ahash::{AHashMap, AHashSet};
+
+use super::value::{Function, Value};
+
+/// the variables of a scope, which functions created in it share rather than copy
+#[derive(Default)]
+pub struct Env {
+    /// the bindings of each name made in this scope, oldest first, since a `let` shadows the
+    /// earlier bindings of a name rather than replacing them
+    vars: RefCell<AHashMap<String, Vec<Binding>>>,
+    /// how many bindings have been made in this scope
+    len: Cell<usize>,
+    /// the scope this one is nested in, as of when this one was created
+    parent: Option<Scope>,
+}
+
+struct Binding {
+    /// how many bindings were made in the scope before this one
+    index: usize,
+    value: Value,
+}
+
+/// a scope as of some point in it, from where the bindings made later aren't visible
+#[derive(Debug, Clone)]
+pub struct Scope {
+    env: Rc<Env>,
+    len: usize,
+}
+
+impl Env {
+    pub fn new(parent: Scope) -> Rc<Self> {
+        Rc::new(Self {
+            vars: RefCell::default(),
+            len: Cell::new(0),
+            parent: Some(parent),
+        })
+    }
+    /// this scope as it is now
+    pub fn scope(self: &Rc<Self>) -> Scope {
+        Scope {
+            env: self.clone(),
+            len: self.len.get(),
+        }
+    }
+
+    pub fn define(&self, name: String, value: Value) {
+        let index = self.len.replace(self.len.get() + 1);
+        let binding = Binding { index, value };
+        self.vars
+            .borrow_mut()
+            .entry(name)
+            .or_default()
+            .push(binding);
+    }
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        self.with_var(name, |v| v.clone())
+    }
+    /// runs `f` on the binding of `name` visible from here. a function doesn't see the bindings
+    /// made after it was created, except for names that weren't bound at all then, for which
+    /// it sees the latest binding, so that it can use what's defined after it
+    pub fn with_var<T>(&self, name: &str, f: impl FnOnce(&mut Value) -> T) -> Option<T> {
+        match self.find(name, Some(self.len.get()), f) {
+            Ok(out) => Some(out),
+            Err(f) => self.find(name, None, f).ok(),
+        }
+    }
+    /// runs `f` on the latest binding of `name` in the innermost scope that has one, only
+    /// counting the first `len` bindings of each scope if given. `f` is given back if there's
+    /// no such binding
+    fn find<T, F: FnOnce(&mut Value) -> T>(
+        &self,
+        name: &str,
+        len: Option<usize>,
+        f: F,
+    ) -> Result<T, F> {
+        if let Some(bindings) = self.vars.borrow_mut().get_mut(name) {
+            let visible = bindings
+                .iter_mut()
+                .rev()
+                .find(|b| len.is_none_or(|len| b.index < len));
+            if let Some(binding) = visible {
+                return Ok(f(&mut binding.value));
+            }
+        }
+        match &self.parent {
+            Some(parent) => parent.env.find(name, len.map(|_| parent.len), f),
+            None => Err(f),
+        }
+    }
+}
+
+impl Scope {
+    /// the same scope once `n` more bindings are made in it
+    pub fn ahead(self, n: usize) -> Self {
+        Self {
+            len: self.len + n,
+            ..self
+        }
+    }
+}
+
+/// only the names, since a function defined in a scope refers back to it
+impl fmt::Debug for Env {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.vars.borrow().keys()).finish()
+    }
+}
+
+/// drops `env`, a scope that was just left, along with the cycles that would otherwise keep it
+/// alive, as a function refers to the scope it was created in and is often stored there.
+///
+/// the scopes and functions reachable from `env` that nothing else refers to are garbage, so
+/// their variables are cleared. anything still referred to from elsewhere, like a function
+/// returned out of the scope, keeps what it reaches alive
+pub fn release(env: Rc<Env>) {
+    // most scopes are only referred to by the interpreter
+    if Rc::strong_count(&env) == 1 {
+        return;
+    }
+    // the scopes `env` is nested in are still in use, so they aren't looked into
+    let mut outside = AHashSet::new();
+    let mut ancestor = env.parent.as_ref();
+    while let Some(a) = ancestor {
+        outside.insert(Rc::as_ptr(&a.env));
+        ancestor = a.env.parent.as_ref();
+    }
+
+    let mut graph = Graph::default();
+    graph.insert(Node::Env(env));
+    let mut i = 0;
+    while i < graph.nodes.len() {
+        let mut targets = vec![];
+        match &graph.nodes[i] {
+            Node::Env(env) => {
+                for binding in env.vars.borrow().values().flatten() {
+                    functions_in(&binding.value, &mut targets);
+                }
+                targets.extend(env.parent.as_ref().map(|p| Node::Env(p.env.clone())));
+            }
+            Node::Function(func) => targets.push(Node::Env(func.captured.env.clone())),
+        }
+        for target in targets {
+            if let Node::Env(env) = &target {
+                if outside.contains(&Rc::as_ptr(env)) {
+                    continue;
+                }
+            }
+            let j = graph.insert(target);
+            graph.edges[i].push(j);
+            graph.internal[j] += 1;
+        }
+        i += 1;
+    }
+
+    // whatever is referred to from outside the graph is alive, as is everything it reaches.
+    // the graph itself holds one reference to each node
+    let mut live: Vec<_> = (0..graph.nodes.len())
+        .map(|i| graph.nodes[i].strong_count() > graph.internal[i] + 1)
+        .collect();
+    let mut stack: Vec<_> = (0..live.len()).filter(|&i| live[i]).collect();
+    while let Some(i) = stack.pop() {
+        for &j in &graph.edges[i] {
+            if !live[j] {
+                live[j] = true;
+                stack.push(j);
+            }
+        }
+    }
+    // everything in the graph is reachable from `env`, so if it's alive nothing is garbage
+    if live[0] {
+        return;
+    }
+    // the graph keeps every node alive until it's dropped, so clearing one can't free another
+    // while its variables are borrowed
+    for (node, live) in graph.nodes.iter().zip(live) {
+        if let (Node::Env(env), false) = (node, live) {
+            env.vars.borrow_mut().clear();
+        }
+    }
+}
+
+/// the scopes and functions reachable from a scope that was left, and the references between them
+#[derive(Default)]
+struct Graph {
+    nodes: Vec<Node>,
+    indices: AHashMap<*const (), usize>,
+    /// the nodes each node refers to, once per reference
+    edges: Vec<Vec<usize>>,
+    /// how many references to each node come from other nodes
+    internal: Vec<usize>,
+}
+enum Node {
+    Env(Rc<Env>),
+    Function(Rc<Function>),
+}
+
+impl Graph {
+    /// the index of `node`, which is added if it isn't in the graph yet
+    fn insert(&mut self, node: Node) -> usize {
+        let ptr = match &node {
+            Node::Env(env) => Rc::as_ptr(env) as *const (),
+            Node::Function(func) => Rc::as_ptr(func) as *const (),
+        };
+        *self.indices.entry(ptr).or_insert_with(|| {
+            self.nodes.push(node);
+            self.edges.push(vec![]);
+            self.internal.push(0);
+            self.nodes.len() - 1
+        })
+    }
+}
+impl Node {
+    fn strong_count(&self) -> usize {
+        match self {
+            Node::Env(env) => Rc::strong_count(env),
+            Node::Function(func) => Rc::strong_count(func),
+        }
+    }
+}
+
+fn functions_in(value: &Value, out: &mut Vec<Node>) {
+    match value {
+        Value::Function(func) => out.push(Node::Function(func.clone())),
+        Value::Array(values) | Value::Tuple(values) => {
+            for value in values {
+                functions_in(value, out);
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,223 @@
+use crate::{error::make_error, sources::span::CodeArea};
+
+make_error! {
+    @kind: Error;
+
+    RuntimeError {
+
+        @title: "Division by zero";
+        @msgs: [
+            area => "Attempted to divide by zero here";
+        ];
+        DivisionByZero {
+            area: CodeArea,
+        }
+
+        @title: "Arithmetic overflow";
+        @msgs: [
+            area => "This operation overflowed";
+        ];
+        Overflow {
+            area: CodeArea,
+        }
+
+        @title: "Invalid integer exponent";
+        @msgs: [
+            area => "Integers can only be raised to non-negative powers that fit in 32 bits";
+        ];
+        InvalidExponent {
+            area: CodeArea,
+        }
+
+        @title: "Invalid shift amount";
+        @msgs: [
+            area => "Integers can only be shifted by 0 to 63 bits";
+        ];
+        InvalidShift {
+            area: CodeArea,
+        }
+
+        @title: "Integer literal out of range";
+        @msgs: [
+            area => "This does not fit in a 64-bit signed integer";
+        ];
+        IntegerOutOfRange {
+            area: CodeArea,
+        }
+
+        @title: format!("Expected bool, found {}", found);
+        @msgs: [
+            area => "This evaluates to {}": found;
+        ];
+        ExpectedBool {
+            found: &'static str,
+            area: CodeArea,
+        }
+
+        @title: format!("Invalid operands for `{}`", op);
+        @msgs: [
+            area => "Cannot apply `{}` to {} and {}": op, left_type, right_type;
+            left_area => "This is of type {}": left_type;
+            right_area => "This is of type {}": right_type;
+        ];
+        TypeMismatch {
+            op: &'static str,
+            left_type: &'static str,
+            right_type: &'static str,
+            area: CodeArea,
+            left_area: CodeArea,
+            right_area: CodeArea,
+        }
+
+        @title: format!("Invalid operand for unary `{}`", op);
+        @msgs: [
+            area => "Cannot apply `{}` to {}": op, typ;
+        ];
+        InvalidUnaryOperand {
+            op: &'static str,
+            typ: &'static str,
+            area: CodeArea,
+        }
+
+        @title: "Imports are not supported yet";
+        @msgs: [
+            area => "Cannot import `{}`": path;
+        ];
+        UnsupportedImport {
+            path: String,
+            area: CodeArea,
+        }
+
+        @title: format!("Cannot iterate over {}", typ);
+        @msgs: [
+            area => "This evaluates to {}, which is not iterable": typ;
+        ];
+        NotIterable {
+            typ: &'static str,
+            area: CodeArea,
+        }
+
+        @title: format!("Cannot call {}", typ);
+        @msgs: [
+            area => "This evaluates to {}, which is not a function": typ;
+        ];
+        NotCallable {
+            typ: &'static str,
+            area: CodeArea,
+        }
+
+        @title: "Wrong number of arguments";
+        @msgs: [
+            area => "Expected {} arguments, found {}": expected, found;
+        ];
+        ArgumentCount {
+            expected: usize,
+            found: usize,
+            area: CodeArea,
+        }
+
+        @title: "Maximum call depth exceeded";
+        @msgs: [
+            area => "Recursion went too deep in this call";
+        ];
+        CallDepth {
+            area: CodeArea,
+        }
+
+        @title: format!("Cannot index into {}", typ);
+        @msgs: [
+            area => "This evaluates to {}": typ;
+        ];
+        NotIndexable {
+            typ: &'static str,
+            area: CodeArea,
+        }
+
+        @title: format!("Cannot index with {}", typ);
+        @msgs: [
+            area => "Expected an int, found {}": typ;
+        ];
+        InvalidIndex {
+            typ: &'static str,
+            area: CodeArea,
+        }
+
+        @title: "Index out of bounds";
+        @msgs: [
+            area => "Index {} is out of bounds for length {}": index, len;
+        ];
+        IndexOutOfBounds {
+            index: i64,
+            len: usize,
+            area: CodeArea,
+        }
+
+        @title: format!("Cannot use {} as a range bound", typ);
+        @msgs: [
+            area => "Expected an int, found {}": typ;
+        ];
+        InvalidRangeBound {
+            typ: &'static str,
+            area: CodeArea,
+        }
+
+        @title: "Invalid slice";
+        @msgs: [
+            area => "Slice starts at {} but ends at {}": start, end;
+        ];
+        InvalidSlice {
+            start: usize,
+            end: usize,
+            area: CodeArea,
+        }
+
+        @title: format!("No field `{}` on {}", field, typ);
+        @msgs: [
+            area => "{} has no field `{}`": typ, field;
+        ];
+        NoSuchField {
+            field: String,
+            typ: &'static str,
+            area: CodeArea,
+        }
+
+        @title: format!("Cannot assign to `.{}`", field);
+        @msgs: [
+            area => "Fields of {} cannot be assigned to": typ;
+        ];
+        ReadOnlyField {
+            field: String,
+            typ: &'static str,
+            area: CodeArea,
+        }
+
+        @title: "Pattern mismatch";
+        @msgs: [
+            area => "Expected {}, found {}": expected, found;
+        ];
+        PatternMismatch {
+            expected: String,
+            found: String,
+            area: CodeArea,
+        }
+
+        @title: "No match arm matched";
+        @msgs: [
+            area => "This evaluates to {}, which no arm matches": found;
+        ];
+        NoMatchingArm {
+            found: String,
+            area: CodeArea,
+        }
+
+        @title: format!("Undefined variable `{}`", name);
+        @msgs: [
+            area => "`{}` is not defined": name;
+        ];
+        UndefinedVariable {
+            name: String,
+            area: CodeArea,
+        }
+
+    }
+}
@@ -0,0 +1,970 @@
+use std::{cmp::Ordering, rc::Rc};
+
+use env::{Env, Scope};
+use error::RuntimeError;
+use value::{FnBody, Function, Value};
+
+use crate::{
+    parser::{
+        ast::{Block, Expr, Item, Pattern, Program, Stmt},
+        operators::{BinOp, LogicalOp, UnaryOp},
+    },
+    sources::{
+        span::{CodeArea, Span, Spanned},
+        SourceKey, SourceMap,
+    },
+};
+
+pub mod env;
+pub mod error;
+#[cfg(test)]
+mod tests;
+pub mod value;
+
+pub type RuntimeResult<T> = Result<T, RuntimeError>;
+
+/// deep recursion would otherwise overflow the native stack
+const MAX_CALL_DEPTH: usize = 1000;
+
+/// anything that stops evaluation of the current expression early
+enum Interrupt {
+    Error(Box<RuntimeError>),
+    Break { label: Option<String>, value: Value },
+    Continue { label: Option<String> },
+}
+impl From<RuntimeError> for Interrupt {
+    fn from(value: RuntimeError) -> Self {
+        Self::Error(Box::new(value))
+    }
+}
+
+type EvalResult<T> = Result<T, Interrupt>;
+
+/// unwraps the result of evaluating something that is not inside any loop
+fn outside_loop(result: EvalResult<Value>) -> RuntimeResult<Value> {
+    match result {
+        Ok(v) => Ok(v),
+        Err(Interrupt::Error(e)) => Err(*e),
+        Err(Interrupt::Break { .. } | Interrupt::Continue { .. }) => {
+            unreachable!("the parser rejects `break` and `continue` outside of loops")
+        }
+    }
+}
+
+/// whether a `break` or `continue` with `target` as its label exits the loop labeled `label`
+fn targets(label: &Option<Spanned<String>>, target: &Option<String>) -> bool {
+    match target {
+        Some(target) => label.as_ref().is_some_and(|l| l.val == *target),
+        None => true,
+    }
+}
+
+/// a location that can be assigned to, with its indices already evaluated
+struct Place<'e> {
+    /// the variable the place is in
+    name: &'e String,
+    span: Span,
+    /// the indices and fields leading from the variable to the place, in the order they apply
+    steps: Vec<(Step<'e>, Span)>,
+}
+enum Step<'e> {
+    Index(Value, Span),
+    Field(Spanned<usize>),
+    Member(&'e Spanned<String>),
+}
+
+pub struct Interpreter<'a> {
+    pub src: SourceKey,
+    sources: &'a SourceMap,
+    /// the innermost scope
+    env: Rc<Env>,
+    call_depth: usize,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(src: SourceKey, sources: &'a SourceMap) -> Self {
+        Self {
+            src,
+            sources,
+            env: Rc::default(),
+            call_depth: 0,
+        }
+    }
+
+    /// runs `f` in a new innermost scope, which is popped again even if `f` fails
+    fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.scoped_in(self.env.scope(), f)
+    }
+    /// runs `f` in a new scope nested in `parent`, and then goes back to the current one
+    fn scoped_in<T>(&mut self, parent: Scope, f: impl FnOnce(&mut Self) -> T) -> T {
+        let outer = std::mem::replace(&mut self.env, Env::new(parent));
+        let out = f(self);
+        let inner = std::mem::replace(&mut self.env, outer);
+        env::release(inner);
+        out
+    }
+    fn define(&mut self, name: String, value: Value) {
+        self.env.define(name, value);
+    }
+
+    /// resolves `target` to the place it denotes, evaluating its indices
+    fn place<'e>(&mut self, target: &'e Spanned<Expr>) -> EvalResult<Place<'e>> {
+        // walk down to the variable, evaluating indices along the way
+        let mut steps = vec![];
+        let mut root = target;
+        let name = loop {
+            let (inner, step) = match &root.val {
+                Expr::Ident(name) => break name,
+                Expr::Index(inner, index) => {
+                    (inner, Step::Index(self.eval_expr(index)?, index.span))
+                }
+                Expr::TupleField(inner, field) => (inner, Step::Field(*field)),
+                Expr::Member(inner, field) => (inner, Step::Member(field)),
+                _ => unreachable!("the parser only allows assigning to place expressions"),
+            };
+            steps.push((step, inner.span));
+            root = inner;
+        };
+        steps.reverse();
+        Ok(Place {
+            name,
+            span: root.span,
+            steps,
+        })
+    }
+    /// runs `f` on the value stored in `place`
+    fn with_slot<T>(&self, place: &Place, f: impl FnOnce(&mut Value) -> T) -> EvalResult<T> {
+        let src = self.src;
+        let walk = |mut slot: &mut Value| -> RuntimeResult<T> {
+            for (step, span) in &place.steps {
+                slot = match (slot, step) {
+                    (Value::Array(v), Step::Index(index, index_span)) => {
+                        let i = array_index(index, v.len(), index_span.to_area(src))?;
+                        &mut v[i]
+                    }
+                    (Value::Tuple(v), Step::Field(field)) => {
+                        v.get_mut(field.val)
+                            .ok_or_else(|| RuntimeError::NoSuchField {
+                                field: field.val.to_string(),
+                                typ: "tuple",
+                                area: field.span.to_area(src),
+                            })?
+                    }
+                    (v, Step::Member(field)) => {
+                        return Err(RuntimeError::ReadOnlyField {
+                            field: field.val.clone(),
+                            typ: v.type_name(),
+                            area: field.span.to_area(src),
+                        })
+                    }
+                    (v, Step::Field(field)) => {
+                        return Err(RuntimeError::NoSuchField {
+                            field: field.val.to_string(),
+                            typ: v.type_name(),
+                            area: field.span.to_area(src),
+                        })
+                    }
+                    (v, Step::Index(..)) => {
+                        return Err(RuntimeError::NotIndexable {
+                            typ: v.type_name(),
+                            area: span.to_area(src),
+                        })
+                    }
+                };
+            }
+            Ok(f(slot))
+        };
+        match self.env.with_var(place.name, walk) {
+            Some(out) => Ok(out?),
+            None => Err(RuntimeError::UndefinedVariable {
+                name: place.name.clone(),
+                area: self.area(place.span),
+            }
+            .into()),
+        }
+    }
+
+    fn area(&self, span: Span) -> CodeArea {
+        span.to_area(self.src)
+    }
+
+    /// runs every item of `program` in order, returning the value of the last one
+    pub fn run_program(&mut self, program: &Program) -> RuntimeResult<Value> {
+        self.hoist_fns(program.items.iter().filter_map(|item| match &item.val.val {
+            Item::Stmt(stmt) => Some(stmt),
+            Item::Import(_) => None,
+        }));
+        let mut out = Value::Unit;
+        for item in &program.items {
+            out = outside_loop(self.eval_item(item))?;
+        }
+        Ok(out)
+    }
+
+    fn eval_item(&mut self, item: &Spanned<Item>) -> EvalResult<Value> {
+        match &item.val {
+            Item::Stmt(stmt) => self.eval_stmt(stmt, item.span),
+            Item::Import(path) => Err(RuntimeError::UnsupportedImport {
+                path: path.val.clone(),
+                area: self.area(item.span),
+            }
+            .into()),
+        }
+    }
+
+    fn eval_expr(&mut self, expr: &Spanned<Expr>) -> EvalResult<Value> {
+        self.eval(&expr.val, expr.span)
+    }
+
+    fn eval(&mut self, expr: &Expr, span: Span) -> EvalResult<Value> {
+        Ok(match expr {
+            Expr::IntegerLiteral(v) => {
+                Value::Int(
+                    i64::try_from(*v).map_err(|_| RuntimeError::IntegerOutOfRange {
+                        area: self.area(span),
+                    })?,
+                )
+            }
+            Expr::FloatLiteral(v) => Value::Float(*v),
+            Expr::StringLiteral(v) => Value::String(v.clone()),
+            Expr::Ident(name) => match self.env.lookup(name) {
+                Some(v) => v,
+                None => {
+                    return Err(RuntimeError::UndefinedVariable {
+                        name: name.clone(),
+                        area: self.area(span),
+                    }
+                    .into())
+                }
+            },
+            Expr::BoolLiteral(v) => Value::Bool(*v),
+            Expr::Logical(left, op, right) => {
+                let l = self.eval_bool(left)?;
+                if l == (*op == LogicalOp::Or) {
+                    Value::Bool(l)
+                } else {
+                    Value::Bool(self.eval_bool(right)?)
+                }
+            }
+            Expr::BinOp(left, op, right) => {
+                let l = self.eval_expr(left)?;
+                let r = self.eval_expr(right)?;
+                self.eval_bin_op(l, *op, r, span, left.span, right.span)?
+            }
+            Expr::UnaryOp(op, inner) => {
+                let v = self.eval_expr(inner)?;
+                self.eval_unary_op(*op, v, span)?
+            }
+            Expr::Assign(target, op, value) => {
+                let mut v = self.eval_expr(value)?;
+                // the indices of the place are only evaluated once, even when it's also read
+                let place = self.place(target)?;
+                if let Some(bin_op) = op.bin_op() {
+                    let current = self.with_slot(&place, |slot| slot.clone())?;
+                    v = self.eval_bin_op(current, bin_op, v, span, target.span, value.span)?;
+                }
+                self.with_slot(&place, |slot| *slot = v)?;
+                Value::Unit
+            }
+            Expr::Block(block) => self.eval_block(block)?,
+            Expr::If { branches, else_ } => {
+                for (cond, block) in branches {
+                    if self.eval_bool(cond)? {
+                        return self.eval_block(block);
+                    }
+                }
+                match else_ {
+                    Some(block) => self.eval_block(block)?,
+                    None => Value::Unit,
+                }
+            }
+            Expr::While { label, cond, body } => loop {
+                if !self.eval_bool(cond)? {
+                    break Value::Unit;
+                }
+                match self.eval_block(body) {
+                    Err(Interrupt::Break {
+                        label: target,
+                        value,
+                    }) if targets(label, &target) => break value,
+                    Err(Interrupt::Continue { label: target }) if targets(label, &target) => {}
+                    r => {
+                        r?;
+                    }
+                }
+            },
+            Expr::For {
+                label,
+                pattern,
+                iter,
+                body,
+            } => {
+                let values: Box<dyn Iterator<Item = Value>> = match self.eval_expr(iter)? {
+                    Value::Array(v) => Box::new(v.into_iter()),
+                    Value::String(s) => Box::new(
+                        s.chars()
+                            .map(|c| Value::String(c.into()))
+                            .collect::<Vec<_>>()
+                            .into_iter(),
+                    ),
+                    Value::Range {
+                        start: Some(start),
+                        end,
+                        inclusive,
+                    } => match end {
+                        Some(end) if inclusive => Box::new((start..=end).map(Value::Int)),
+                        Some(end) => Box::new((start..end).map(Value::Int)),
+                        None => Box::new((start..=i64::MAX).map(Value::Int)),
+                    },
+                    Value::Range { start: None, .. } => {
+                        return Err(RuntimeError::NotIterable {
+                            typ: "a range with no start",
+                            area: self.area(iter.span),
+                        }
+                        .into())
+                    }
+                    v => {
+                        return Err(RuntimeError::NotIterable {
+                            typ: v.type_name(),
+                            area: self.area(iter.span),
+                        }
+                        .into())
+                    }
+                };
+                let mut out = Value::Unit;
+                for v in values {
+                    let r = self.scoped(|slef| {
+                        slef.bind_pattern(pattern, v)?;
+                        slef.eval_block(body)
+                    });
+                    match r {
+                        Err(Interrupt::Break {
+                            label: target,
+                            value,
+                        }) if targets(label, &target) => {
+                            out = value;
+                            break;
+                        }
+                        Err(Interrupt::Continue { label: target }) if targets(label, &target) => {}
+                        r => {
+                            r?;
+                        }
+                    }
+                }
+                out
+            }
+            Expr::Break { label, value } => {
+                let value = match value {
+                    Some(v) => self.eval_expr(v)?,
+                    None => Value::Unit,
+                };
+                return Err(Interrupt::Break {
+                    label: label.as_ref().map(|l| l.val.clone()),
+                    value,
+                });
+            }
+            Expr::Continue { label } => {
+                return Err(Interrupt::Continue {
+                    label: label.as_ref().map(|l| l.val.clone()),
+                })
+            }
+            Expr::Dbg(inner) => {
+                let v = self.eval_expr(inner)?;
+                let source = &self.sources[self.src];
+                let (line, col) = source.line_col(inner.span.start);
+                eprintln!(
+                    "{}:{}:{}: {} = {}",
+                    source.path.display(),
+                    line,
+                    col,
+                    &source.content[inner.span.start..inner.span.end],
+                    v.repr(),
+                );
+                v
+            }
+            Expr::Lambda { params, body } => Value::Function(Rc::new(Function {
+                name: None,
+                params: params.clone(),
+                body: FnBody::Expr(body.clone()),
+                captured: self.env.scope(),
+            })),
+            Expr::Call(callee, args) => {
+                let func = match self.eval_expr(callee)? {
+                    Value::Function(f) => f,
+                    v => {
+                        return Err(RuntimeError::NotCallable {
+                            typ: v.type_name(),
+                            area: self.area(callee.span),
+                        }
+                        .into())
+                    }
+                };
+                let args = args
+                    .iter()
+                    .map(|a| self.eval_expr(a))
+                    .collect::<EvalResult<Vec<_>>>()?;
+                self.call(func, args, span)?
+            }
+            Expr::Index(target, index) => {
+                let v = self.eval_expr(target)?;
+                let i = self.eval_expr(index)?;
+                let area = self.area(index.span);
+                match (v, i) {
+                    (
+                        Value::Array(v),
+                        Value::Range {
+                            start,
+                            end,
+                            inclusive,
+                        },
+                    ) => {
+                        let (from, to) = slice_range(start, end, inclusive, v.len(), area)?;
+                        Value::Array(v[from..to].to_vec())
+                    }
+                    (
+                        Value::String(s),
+                        Value::Range {
+                            start,
+                            end,
+                            inclusive,
+                        },
+                    ) => {
+                        let len = s.chars().count();
+                        let (from, to) = slice_range(start, end, inclusive, len, area)?;
+                        Value::String(s.chars().skip(from).take(to - from).collect())
+                    }
+                    (Value::Array(mut v), i) => {
+                        let i = array_index(&i, v.len(), area)?;
+                        v.swap_remove(i)
+                    }
+                    (Value::String(s), i) => {
+                        let i = array_index(&i, s.chars().count(), area)?;
+                        Value::String(s.chars().nth(i).unwrap().into())
+                    }
+                    (v, _) => {
+                        return Err(RuntimeError::NotIndexable {
+                            typ: v.type_name(),
+                            area: self.area(target.span),
+                        }
+                        .into())
+                    }
+                }
+            }
+            Expr::TupleField(target, field) => match self.eval_expr(target)? {
+                Value::Tuple(mut v) if field.val < v.len() => v.swap_remove(field.val),
+                v => {
+                    return Err(RuntimeError::NoSuchField {
+                        field: field.val.to_string(),
+                        typ: v.type_name(),
+                        area: self.area(field.span),
+                    }
+                    .into())
+                }
+            },
+            Expr::Member(target, field) => match (self.eval_expr(target)?, field.val.as_str()) {
+                (Value::Array(v) | Value::Tuple(v), "len") => Value::Int(v.len() as i64),
+                (Value::String(s), "len") => Value::Int(s.chars().count() as i64),
+                (v, _) => {
+                    return Err(RuntimeError::NoSuchField {
+                        field: field.val.clone(),
+                        typ: v.type_name(),
+                        area: self.area(field.span),
+                    }
+                    .into())
+                }
+            },
+            Expr::Match { scrutinee, arms } => {
+                let value = self.eval_expr(scrutinee)?;
+                for arm in arms {
+                    let mut bindings = vec![];
+                    if self
+                        .match_pattern(&arm.pattern, value.clone(), &mut bindings)?
+                        .is_some()
+                    {
+                        continue;
+                    }
+                    let out = self.scoped(|slef| {
+                        for (name, value) in bindings {
+                            slef.define(name, value);
+                        }
+                        if let Some(guard) = &arm.guard {
+                            if !slef.eval_bool(guard)? {
+                                return Ok(None);
+                            }
+                        }
+                        slef.eval_expr(&arm.body).map(Some)
+                    })?;
+                    if let Some(out) = out {
+                        return Ok(out);
+                    }
+                }
+                return Err(RuntimeError::NoMatchingArm {
+                    found: value.repr(),
+                    area: self.area(scrutinee.span),
+                }
+                .into());
+            }
+            Expr::Array(elems) => Value::Array(
+                elems
+                    .iter()
+                    .map(|e| self.eval_expr(e))
+                    .collect::<EvalResult<_>>()?,
+            ),
+            Expr::Tuple(elems) if elems.is_empty() => Value::Unit,
+            Expr::Tuple(elems) => Value::Tuple(
+                elems
+                    .iter()
+                    .map(|e| self.eval_expr(e))
+                    .collect::<EvalResult<_>>()?,
+            ),
+            Expr::Range { start, end } => Value::Range {
+                start: self.eval_range_bound(start.as_deref())?,
+                end: self.eval_range_bound(end.bound())?,
+                inclusive: end.is_inclusive(),
+            },
+            Expr::Error => unreachable!("erroneous expressions should never be evaluated"),
+        })
+    }
+
+    fn eval_range_bound(&mut self, bound: Option<&Spanned<Expr>>) -> EvalResult<Option<i64>> {
+        let Some(bound) = bound else {
+            return Ok(None);
+        };
+        match self.eval_expr(bound)? {
+            Value::Int(v) => Ok(Some(v)),
+            v => Err(RuntimeError::InvalidRangeBound {
+                typ: v.type_name(),
+                area: self.area(bound.span),
+            }
+            .into()),
+        }
+    }
+    fn eval_bool(&mut self, expr: &Spanned<Expr>) -> EvalResult<bool> {
+        match self.eval_expr(expr)? {
+            Value::Bool(b) => Ok(b),
+            v => Err(RuntimeError::ExpectedBool {
+                found: v.type_name(),
+                area: self.area(expr.span),
+            }
+            .into()),
+        }
+    }
+
+    fn eval_stmt(&mut self, stmt: &Stmt, span: Span) -> EvalResult<Value> {
+        match stmt {
+            Stmt::Expr(e) => self.eval(e, span),
+            Stmt::Let(pattern, value) => {
+                let value = self.eval_expr(value)?;
+                self.bind_pattern(pattern, value)?;
+                Ok(Value::Unit)
+            }
+            // already defined when the enclosing block started
+            Stmt::Fn(_) => Ok(Value::Unit),
+        }
+    }
+    /// defines the functions declared among `stmts` before running any of them, so that they
+    /// can call each other whatever order they're in
+    fn hoist_fns<'s>(&mut self, stmts: impl Iterator<Item = &'s Stmt>) {
+        let decls: Vec<_> = stmts
+            .filter_map(|stmt| match stmt {
+                Stmt::Fn(decl) => Some(decl),
+                _ => None,
+            })
+            .collect();
+        // the functions capture the scope they're defined in as of after all of them, so they
+        // can call themselves and each other
+        let captured = self.env.scope().ahead(decls.len());
+        for decl in decls {
+            let func = Function {
+                name: Some(decl.name.val.clone()),
+                params: decl.params.clone(),
+                body: FnBody::Block(decl.body.clone()),
+                captured: captured.clone(),
+            };
+            self.define(decl.name.val.clone(), Value::Function(Rc::new(func)));
+        }
+    }
+
+    fn eval_block(&mut self, block: &Spanned<Block>) -> EvalResult<Value> {
+        self.scoped(|slef| {
+            slef.hoist_fns(
+                block
+                    .normal
+                    .iter()
+                    .chain(&block.ret)
+                    .map(|stmt| &stmt.val.val),
+            );
+            for stmt in &block.normal {
+                slef.eval_stmt(&stmt.val, stmt.span)?;
+            }
+            match &block.ret {
+                Some(stmt) => slef.eval_stmt(&stmt.val, stmt.span),
+                None => Ok(Value::Unit),
+            }
+        })
+    }
+
+    fn call(&mut self, func: Rc<Function>, args: Vec<Value>, span: Span) -> RuntimeResult<Value> {
+        if args.len() != func.params.len() {
+            return Err(RuntimeError::ArgumentCount {
+                expected: func.params.len(),
+                found: args.len(),
+                area: self.area(span),
+            });
+        }
+        if self.call_depth == MAX_CALL_DEPTH {
+            return Err(RuntimeError::CallDepth {
+                area: self.area(span),
+            });
+        }
+
+        self.call_depth += 1;
+        let out = self.scoped_in(func.captured.clone(), |slef| {
+            for (param, arg) in func.params.iter().zip(args) {
+                slef.bind_pattern(param, arg)?;
+            }
+            match &func.body {
+                FnBody::Block(block) => slef.eval_block(block),
+                FnBody::Expr(expr) => slef.eval_expr(expr),
+            }
+        });
+        self.call_depth -= 1;
+
+        outside_loop(out)
+    }
+
+    /// destructures `value` according to `pattern`, defining every bound name in the current scope
+    fn bind_pattern(&mut self, pattern: &Spanned<Pattern>, value: Value) -> EvalResult<()> {
+        let mut bindings = vec![];
+        if let Some(mismatch) = self.match_pattern(pattern, value, &mut bindings)? {
+            return Err(mismatch.into());
+        }
+        for (name, value) in bindings {
+            self.define(name, value);
+        }
+        Ok(())
+    }
+    /// matches `value` against `pattern`, collecting the names it binds into `bindings`.
+    /// if it doesn't match, returns the error to report where the pattern has to match
+    fn match_pattern(
+        &mut self,
+        pattern: &Spanned<Pattern>,
+        value: Value,
+        bindings: &mut Vec<(String, Value)>,
+    ) -> EvalResult<Option<RuntimeError>> {
+        let mismatch = |slef: &Self, expected: String, found: String| {
+            Ok(Some(RuntimeError::PatternMismatch {
+                expected,
+                found,
+                area: slef.area(pattern.span),
+            }))
+        };
+
+        match (&pattern.val, value) {
+            (Pattern::Ident(name), value) => bindings.push((name.clone(), value)),
+            (Pattern::Wildcard | Pattern::Rest, _) => {}
+            (Pattern::Literal(lit), value) => {
+                let lit = self.eval_expr(lit)?;
+                if lit != value {
+                    return mismatch(self, lit.repr(), value.repr());
+                }
+            }
+            (
+                Pattern::Range {
+                    start,
+                    end: range_end,
+                },
+                value,
+            ) => {
+                let inclusive = range_end.is_inclusive();
+                let start = match start {
+                    Some(start) => Some(self.eval_expr(start)?),
+                    None => None,
+                };
+                let end = match range_end.bound() {
+                    Some(end) => Some(self.eval_expr(end)?),
+                    None => None,
+                };
+                let above_start = match &start {
+                    Some(start) => pattern_cmp(start, &value).is_some_and(|o| o.is_le()),
+                    None => true,
+                };
+                let below_end = match &end {
+                    Some(end) => pattern_cmp(&value, end).is_some_and(|o| {
+                        if inclusive {
+                            o.is_le()
+                        } else {
+                            o.is_lt()
+                        }
+                    }),
+                    None => true,
+                };
+                if !(above_start && below_end) {
+                    let expected = format!(
+                        "value in {}{}{}",
+                        start.map(|s| s.repr()).unwrap_or_default(),
+                        if inclusive { "..=" } else { ".." },
+                        end.map(|e| e.repr()).unwrap_or_default(),
+                    );
+                    return mismatch(self, expected, value.repr());
+                }
+            }
+            (Pattern::Tuple(pats), Value::Tuple(values)) => {
+                return self.match_list_pattern(pattern.span, pats, values, "tuple", bindings)
+            }
+            (Pattern::Tuple(pats), Value::Unit) if pats.is_empty() => {}
+            (Pattern::Array(pats), Value::Array(values)) => {
+                return self.match_list_pattern(pattern.span, pats, values, "array", bindings)
+            }
+            (Pattern::Tuple(pats) | Pattern::Array(pats), value) => {
+                let kind = if matches!(pattern.val, Pattern::Tuple(_)) {
+                    "tuple"
+                } else {
+                    "array"
+                };
+                return mismatch(
+                    self,
+                    list_pattern_desc(kind, pats),
+                    value.type_name().into(),
+                );
+            }
+        }
+        Ok(None)
+    }
+    fn match_list_pattern(
+        &mut self,
+        span: Span,
+        pats: &[Spanned<Pattern>],
+        values: Vec<Value>,
+        kind: &str,
+        bindings: &mut Vec<(String, Value)>,
+    ) -> EvalResult<Option<RuntimeError>> {
+        let rest = pats.iter().position(|p| p.val == Pattern::Rest);
+        let fixed = pats.len() - rest.is_some() as usize;
+
+        let fits = match rest {
+            Some(_) => values.len() >= fixed,
+            None => values.len() == fixed,
+        };
+        if !fits {
+            return Ok(Some(RuntimeError::PatternMismatch {
+                expected: list_pattern_desc(kind, pats),
+                found: format!("{} with {} elements", kind, values.len()),
+                area: self.area(span),
+            }));
+        }
+
+        let skipped = values.len() - fixed;
+        let mut values = values.into_iter();
+        for (i, pat) in pats.iter().enumerate() {
+            if Some(i) == rest {
+                values.by_ref().take(skipped).for_each(drop);
+            } else if let Some(mismatch) =
+                self.match_pattern(pat, values.next().unwrap(), bindings)?
+            {
+                return Ok(Some(mismatch));
+            }
+        }
+        Ok(None)
+    }
+
+    fn eval_bin_op(
+        &self,
+        left: Value,
+        op: BinOp,
+        right: Value,
+        span: Span,
+        left_span: Span,
+        right_span: Span,
+    ) -> RuntimeResult<Value> {
+        let mismatch = RuntimeError::TypeMismatch {
+            op: op.name(),
+            left_type: left.type_name(),
+            right_type: right.type_name(),
+            area: self.area(span),
+            left_area: self.area(left_span),
+            right_area: self.area(right_span),
+        };
+        let overflow = || RuntimeError::Overflow {
+            area: self.area(span),
+        };
+        let div_zero = || RuntimeError::DivisionByZero {
+            area: self.area(right_span),
+        };
+
+        if let BinOp::Eq | BinOp::NEq = op {
+            if std::mem::discriminant(&left) != std::mem::discriminant(&right) {
+                return Err(mismatch);
+            }
+            return Ok(Value::Bool((left == right) == (op == BinOp::Eq)));
+        }
+        if let BinOp::Lt | BinOp::Gt | BinOp::LtE | BinOp::GtE = op {
+            let ord = match (&left, &right) {
+                (Value::Int(a), Value::Int(b)) => a.partial_cmp(b),
+                (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+                (Value::String(a), Value::String(b)) => a.partial_cmp(b),
+                _ => return Err(mismatch),
+            };
+            // comparisons involving NaN are always false
+            return Ok(Value::Bool(ord.is_some_and(|ord| match op {
+                BinOp::Lt => ord.is_lt(),
+                BinOp::Gt => ord.is_gt(),
+                BinOp::LtE => ord.is_le(),
+                _ => ord.is_ge(),
+            })));
+        }
+
+        Ok(match (left, right) {
+            (Value::Int(a), Value::Int(b)) => Value::Int(match op {
+                BinOp::Plus => a.checked_add(b).ok_or_else(overflow)?,
+                BinOp::Minus => a.checked_sub(b).ok_or_else(overflow)?,
+                BinOp::Asterisk => a.checked_mul(b).ok_or_else(overflow)?,
+                BinOp::Div | BinOp::Mod if b == 0 => return Err(div_zero()),
+                BinOp::Div => a.checked_div(b).ok_or_else(overflow)?,
+                BinOp::Mod => a.checked_rem(b).ok_or_else(overflow)?,
+                BinOp::Pow => {
+                    let exp = u32::try_from(b).map_err(|_| RuntimeError::InvalidExponent {
+                        area: self.area(right_span),
+                    })?;
+                    a.checked_pow(exp).ok_or_else(overflow)?
+                }
+                BinOp::BitAnd => a & b,
+                BinOp::BitOr => a | b,
+                BinOp::BitXor => a ^ b,
+                BinOp::ShiftLeft | BinOp::ShiftRight => {
+                    let amount = u32::try_from(b)
+                        .ok()
+                        .filter(|&b| b < i64::BITS)
+                        .ok_or_else(|| RuntimeError::InvalidShift {
+                            area: self.area(right_span),
+                        })?;
+                    if op == BinOp::ShiftLeft {
+                        a << amount
+                    } else {
+                        a >> amount
+                    }
+                }
+                _ => return Err(mismatch),
+            }),
+            (Value::Float(a), Value::Float(b)) => Value::Float(match op {
+                BinOp::Plus => a + b,
+                BinOp::Minus => a - b,
+                BinOp::Asterisk => a * b,
+                BinOp::Div | BinOp::Mod if b == 0.0 => return Err(div_zero()),
+                BinOp::Div => a / b,
+                BinOp::Mod => a % b,
+                BinOp::Pow => a.powf(b),
+                _ => return Err(mismatch),
+            }),
+            (Value::Bool(a), Value::Bool(b)) => Value::Bool(match op {
+                BinOp::BitAnd => a & b,
+                BinOp::BitOr => a | b,
+                BinOp::BitXor => a ^ b,
+                _ => return Err(mismatch),
+            }),
+            (Value::String(a), Value::String(b)) if op == BinOp::Plus => Value::String(a + &b),
+            (Value::Array(mut a), Value::Array(b)) if op == BinOp::Plus => {
+                a.extend(b);
+                Value::Array(a)
+            }
+            _ => return Err(mismatch),
+        })
+    }
+
+    fn eval_unary_op(&self, op: UnaryOp, value: Value, span: Span) -> RuntimeResult<Value> {
+        Ok(match (op, value) {
+            (UnaryOp::Minus, Value::Int(v)) => {
+                Value::Int(v.checked_neg().ok_or_else(|| RuntimeError::Overflow {
+                    area: self.area(span),
+                })?)
+            }
+            (UnaryOp::Minus, Value::Float(v)) => Value::Float(-v),
+            (UnaryOp::Not, Value::Bool(v)) => Value::Bool(!v),
+            (UnaryOp::BitNot, Value::Int(v)) => Value::Int(!v),
+            (op, value) => {
+                return Err(RuntimeError::InvalidUnaryOperand {
+                    op: op.name(),
+                    typ: value.type_name(),
+                    area: self.area(span),
+                })
+            }
+        })
+    }
+}
+
+fn list_pattern_desc(kind: &str, pats: &[Spanned<Pattern>]) -> String {
+    let rest = pats.iter().any(|p| p.val == Pattern::Rest);
+    let fixed = pats.len() - rest as usize;
+    if rest {
+        format!("{} with at least {} elements", kind, fixed)
+    } else {
+        format!("{} with {} elements", kind, fixed)
+    }
+}
+
+/// orders two values of the same type for range patterns, `None` if they can't be compared
+fn pattern_cmp(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Int(a), Value::Int(b)) => a.partial_cmp(b),
+        (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+        (Value::String(a), Value::String(b)) => a.partial_cmp(b),
+        _ => None,
+    }
+}
+
+/// resolves a range used as an index into the `from..to` it covers
+fn slice_range(
+    start: Option<i64>,
+    end: Option<i64>,
+    inclusive: bool,
+    len: usize,
+    area: CodeArea,
+) -> RuntimeResult<(usize, usize)> {
+    let from = match start {
+        Some(start) => slice_bound(&Value::Int(start), len, area)?,
+        None => 0,
+    };
+    let to = match end {
+        Some(end) if inclusive => slice_bound(&Value::Int(end.saturating_add(1)), len, area)?,
+        Some(end) => slice_bound(&Value::Int(end), len, area)?,
+        None => len,
+    };
+    if from > to {
+        return Err(RuntimeError::InvalidSlice {
+            start: from,
+            end: to,
+            area,
+        });
+    }
+    Ok((from, to))
+}
+
+/// checks that `index` is an int in `0..len`
+fn array_index(index: &Value, len: usize, area: CodeArea) -> RuntimeResult<usize> {
+    checked_index(index, len, area, false)
+}
+/// checks that `index` is an int in `0..=len`, since a slice may end one past the last element
+fn slice_bound(index: &Value, len: usize, area: CodeArea) -> RuntimeResult<usize> {
+    checked_index(index, len, area, true)
+}
+fn checked_index(
+    index: &Value,
+    len: usize,
+    area: CodeArea,
+    inclusive: bool,
+) -> RuntimeResult<usize> {
+    match index {
+        Value::Int(i) => usize::try_from(*i)
+            .ok()
+            .filter(|i| *i < len || (inclusive && *i == len))
+            .ok_or(RuntimeError::IndexOutOfBounds {
+                index: *i,
+                len,
+                area,
+            }),
+        v => Err(RuntimeError::InvalidIndex {
+            typ: v.type_name(),
+            area,
+        }),
+    }
+}
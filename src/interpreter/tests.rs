@@ -0,0 +1,277 @@
+use std::rc::Rc;
+
+use crate::{
+    parser::{ast::Expr, Parser},
+    sources::{
+        span::{Span, Spannable},
+        Source, SourceMap,
+    },
+};
+
+use super::{
+    env::{self, Env},
+    error::RuntimeError,
+    value::{FnBody, Function, Value},
+    Interpreter, RuntimeResult,
+};
+
+fn run(code: &str) -> RuntimeResult<Value> {
+    let mut sources = SourceMap::default();
+    let mut parser = Parser::new(
+        Source {
+            path: "test.shid".into(),
+            content: code.into(),
+        },
+        &mut sources,
+    );
+    let program = parser.parse_program();
+    assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+    let src = parser.src;
+    Interpreter::new(src, &sources).run_program(&program)
+}
+
+#[test]
+fn compound_assignment_evaluates_indices_once() {
+    let code = "
+        let a = [[0, 0], [0, 0]];
+        let i = 0;
+        a[{ i += 1; 1 }][{ i += 1; 0 }] += 5;
+        (a, i)
+    ";
+    let a = Value::Array(vec![
+        Value::Array(vec![Value::Int(0), Value::Int(0)]),
+        Value::Array(vec![Value::Int(5), Value::Int(0)]),
+    ]);
+    assert_eq!(run(code).unwrap(), Value::Tuple(vec![a, Value::Int(2)]));
+}
+
+#[test]
+fn functions_see_later_declarations() {
+    let code = "
+        fn a() { b() + x }
+        fn b() { 1 }
+        let x = 2;
+        let f = () => { fn c() { d() } fn d() { x } c() };
+        x = 3;
+        (a(), f())
+    ";
+    assert_eq!(
+        run(code).unwrap(),
+        Value::Tuple(vec![Value::Int(4), Value::Int(3)])
+    );
+}
+
+#[test]
+fn closures_share_variables() {
+    let code = "
+        let n = 0;
+        let inc = () => { n += 1; n };
+        inc();
+        inc();
+        n
+    ";
+    assert_eq!(run(code).unwrap(), Value::Int(2));
+}
+
+#[test]
+fn closures_dont_see_later_shadowing() {
+    let code = "
+        let x = 1;
+        let f = () => x;
+        let g = { let y = x; () => (x, y) };
+        let x = \"s\";
+        let y = 2;
+        x = \"t\";
+        (f(), g(), x)
+    ";
+    assert_eq!(
+        run(code).unwrap(),
+        Value::Tuple(vec![
+            Value::Int(1),
+            Value::Tuple(vec![Value::Int(1), Value::Int(1)]),
+            Value::String("t".into()),
+        ])
+    );
+}
+
+#[test]
+fn scopes_only_their_functions_refer_to_are_freed() {
+    let func = |captured| {
+        Value::Function(Rc::new(Function {
+            name: None,
+            params: Rc::default(),
+            body: FnBody::Expr(Rc::new(Expr::Tuple(vec![]).spanned(Span::new(0, 0)))),
+            captured,
+        }))
+    };
+    let global = Rc::new(Env::default());
+
+    // a function stored in the scope it was created in, and one in a scope nested in that
+    let env = Env::new(global.scope());
+    env.define("f".into(), func(env.scope()));
+    let g = func(Env::new(env.scope()).scope());
+    env.define("g".into(), Value::Array(vec![g]));
+    let weak = Rc::downgrade(&env);
+    env::release(env);
+    assert!(weak.upgrade().is_none());
+
+    // a function that's still around keeps its scope alive
+    let env = Env::new(global.scope());
+    let f = func(env.scope());
+    env.define("f".into(), f.clone());
+    let weak = Rc::downgrade(&env);
+    env::release(env);
+    assert!(weak.upgrade().is_some());
+    assert_eq!(weak.upgrade().unwrap().lookup("f"), Some(f));
+}
+
+#[test]
+fn division_by_zero() {
+    for code in ["1 / 0", "1 % 0", "let x = 5; x /= 0"] {
+        assert!(
+            matches!(run(code), Err(RuntimeError::DivisionByZero { .. })),
+            "{code}"
+        );
+    }
+}
+
+#[test]
+fn ints_and_floats_dont_mix() {
+    for code in ["1 + 1.0", "2.5 * 2", "1 < 1.5"] {
+        assert!(
+            matches!(run(code), Err(RuntimeError::TypeMismatch { .. })),
+            "{code}"
+        );
+    }
+}
+
+#[test]
+fn integers_out_of_range() {
+    assert_eq!(run("9223372036854775807").unwrap(), Value::Int(i64::MAX));
+    for (code, span) in [
+        ("9223372036854775808", 0..19),
+        ("1 + 0xffff_ffff_ffff_ffff", 4..25),
+    ] {
+        match run(code) {
+            Err(RuntimeError::IntegerOutOfRange { area }) => {
+                assert_eq!(area.span, span.into(), "{code}")
+            }
+            result => panic!("{code}: {result:?}"),
+        }
+    }
+}
+
+#[test]
+fn exponents() {
+    for (code, value) in [("2 ** 3 ** 2", 512), ("-2 ** 2", -4), ("(-2) ** 3", -8)] {
+        assert_eq!(run(code).unwrap(), Value::Int(value), "{code}");
+    }
+    match run("let x = 2 ** -1") {
+        Err(RuntimeError::InvalidExponent { area }) => assert_eq!(area.span, Span::new(13, 15)),
+        result => panic!("{result:?}"),
+    }
+    match run("1 + 2 ** 63") {
+        Err(RuntimeError::Overflow { area }) => assert_eq!(area.span, Span::new(4, 11)),
+        result => panic!("{result:?}"),
+    }
+}
+
+#[test]
+fn bitwise_ops() {
+    for (code, value) in [
+        ("1 & 3 == 1", Value::Bool(true)),
+        ("6 & 3 ^ 1 | 8", Value::Int(11)),
+        ("1 << 2 + 1", Value::Int(8)),
+        ("-16 >> 2", Value::Int(-4)),
+        ("~0", Value::Int(-1)),
+    ] {
+        assert_eq!(run(code).unwrap(), value, "{code}");
+    }
+    match run("1 << 64") {
+        Err(RuntimeError::InvalidShift { area }) => assert_eq!(area.span, Span::new(5, 7)),
+        result => panic!("{result:?}"),
+    }
+}
+
+#[test]
+fn logical_ops_short_circuit() {
+    let code = "
+        let n = 0;
+        let a = false && { n += 1; true };
+        let b = true || { n += 10; false };
+        let c = true && { n += 100; false };
+        (a, b, c, n)
+    ";
+    assert_eq!(
+        run(code).unwrap(),
+        Value::Tuple(vec![
+            Value::Bool(false),
+            Value::Bool(true),
+            Value::Bool(false),
+            Value::Int(100),
+        ])
+    );
+    assert!(matches!(
+        run("false || 1"),
+        Err(RuntimeError::ExpectedBool { .. })
+    ));
+}
+
+#[test]
+fn labeled_break_and_continue() {
+    let code = "
+        let pairs = [];
+        'outer: for i in 0..4 {
+            'inner: for j in 0..4 {
+                if j > i { continue 'outer; }
+                if i == 3 { break 'outer; }
+                if j == 1 { continue 'inner; }
+                pairs += [(i, j)];
+            }
+        }
+        pairs
+    ";
+    let pair = |i, j| Value::Tuple(vec![Value::Int(i), Value::Int(j)]);
+    assert_eq!(
+        run(code).unwrap(),
+        Value::Array(vec![pair(0, 0), pair(1, 0), pair(2, 0), pair(2, 2)])
+    );
+}
+
+#[test]
+fn slicing() {
+    let ints = |v: &[i64]| Value::Array(v.iter().map(|&i| Value::Int(i)).collect());
+    let cases = [
+        ("[1, 2, 3, 4][1..3]", ints(&[2, 3])),
+        ("[1, 2, 3, 4][1..=3]", ints(&[2, 3, 4])),
+        ("[1, 2, 3, 4][..2]", ints(&[1, 2])),
+        ("[1, 2, 3, 4][2..]", ints(&[3, 4])),
+        ("[1, 2, 3, 4][4..]", ints(&[])),
+        ("\"héllo\"[1..4]", Value::String("éll".into())),
+    ];
+    for (code, expected) in cases {
+        assert_eq!(run(code).unwrap(), expected, "{code}");
+    }
+    assert!(matches!(
+        run("[1, 2][1..5]"),
+        Err(RuntimeError::IndexOutOfBounds { .. })
+    ));
+    assert!(matches!(
+        run("[1, 2][2..1]"),
+        Err(RuntimeError::InvalidSlice { .. })
+    ));
+}
+
+#[test]
+fn pattern_mismatch() {
+    for code in ["let (a, b) = (1, 2, 3)", "let [a, b] = [1]", "let 1 = 2"] {
+        assert!(
+            matches!(run(code), Err(RuntimeError::PatternMismatch { .. })),
+            "{code}"
+        );
+    }
+    assert!(matches!(
+        run("match 5 { 0..5 => 1, x if x > 5 => 2 }"),
+        Err(RuntimeError::NoMatchingArm { .. })
+    ));
+}
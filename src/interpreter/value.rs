@@ -0,0 +1,130 @@
+use std::{fmt::Display, rc::Rc};
+
+use super::env::Scope;
+
+use crate::{
+    parser::ast::{Block, Expr, Pattern},
+    sources::span::Spanned,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    String(String),
+    Bool(bool),
+
+    Array(Vec<Value>),
+    Tuple(Vec<Value>),
+    Range {
+        start: Option<i64>,
+        end: Option<i64>,
+        inclusive: bool,
+    },
+
+    Function(Rc<Function>),
+
+    Unit,
+}
+
+#[derive(Debug)]
+pub struct Function {
+    /// `None` for lambdas
+    pub name: Option<String>,
+    pub params: Rc<Vec<Spanned<Pattern>>>,
+    pub body: FnBody,
+    /// the scope the function was created in, shared with everything else created there
+    pub captured: Scope,
+}
+/// the body of a function, shared with the ast it comes from
+#[derive(Debug)]
+pub enum FnBody {
+    Block(Rc<Spanned<Block>>),
+    Expr(Rc<Spanned<Expr>>),
+}
+/// functions are only equal to themselves
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "int",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+            Value::Bool(_) => "bool",
+            Value::Array(_) => "array",
+            Value::Tuple(_) => "tuple",
+            Value::Range { .. } => "range",
+            Value::Function(_) => "function",
+            Value::Unit => "unit",
+        }
+    }
+
+    /// like `Display`, but shows strings quoted and escaped
+    pub fn repr(&self) -> String {
+        match self {
+            Value::String(s) => format!("{:?}", s),
+            v => v.to_string(),
+        }
+    }
+}
+
+fn write_list(f: &mut std::fmt::Formatter<'_>, values: &[Value]) -> std::fmt::Result {
+    for (i, v) in values.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        match v {
+            Value::String(s) => write!(f, "{:?}", s)?,
+            v => write!(f, "{}", v)?,
+        }
+    }
+    Ok(())
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(v) => write!(f, "{}", v),
+            Value::Float(v) => write!(f, "{:?}", v),
+            Value::String(v) => write!(f, "{}", v),
+            Value::Bool(v) => write!(f, "{}", v),
+            Value::Array(v) => {
+                write!(f, "[")?;
+                write_list(f, v)?;
+                write!(f, "]")
+            }
+            Value::Tuple(v) => {
+                write!(f, "(")?;
+                write_list(f, v)?;
+                if v.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+            Value::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                if let Some(start) = start {
+                    write!(f, "{}", start)?;
+                }
+                write!(f, "{}", if *inclusive { "..=" } else { ".." })?;
+                if let Some(end) = end {
+                    write!(f, "{}", end)?;
+                }
+                Ok(())
+            }
+            Value::Function(func) => match &func.name {
+                Some(name) => write!(f, "<function {}>", name),
+                None => write!(f, "<lambda>"),
+            },
+            Value::Unit => write!(f, "()"),
+        }
+    }
+}
@@ -1,19 +1,165 @@
-use parser::Parser;
+use interpreter::{value::Value, Interpreter};
+use parser::{printer::format_program, Parser};
 use sources::{Source, SourceMap};
 
+mod diff;
 mod error;
+mod interpreter;
 mod parser;
 mod sources;
 
+/// the interpreter recurses on the native stack, so it gets a thread with plenty of room, where
+/// the program is parsed too as the ast isn't `Send`
+const INTERPRETER_STACK_SIZE: usize = 256 * 1024 * 1024;
+
+const USAGE: &str = "usage:
+    shid [file]    run a file (defaults to glib.shid)
+    shid ops       print the operator precedence table
+    shid fmt [--check] <file>...
+                   format files in place, or with `--check` show how the ones that
+                   aren't formatted would change and fail";
+
 fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["ops"] => print_ops(),
+        ["fmt", ref args @ ..] => format_files(args),
+        ["-h" | "--help"] => println!("{USAGE}"),
+        [] => run_file("glib.shid"),
+        [path] => run_file(path),
+        _ => {
+            eprintln!("{USAGE}");
+            std::process::exit(2);
+        }
+    }
+}
+
+fn print_ops() {
+    let table = parser::operators::precedence_table();
+
+    println!("{:<7}{:<20}operators", "level", "associativity");
+    for (level, (typ, names)) in table.iter().enumerate().rev() {
+        println!("{:<7}{:<20}{}", level + 1, typ.name(), names.join(" "));
+    }
+    let assign = parser::operators::ASSIGN_LIST
+        .iter()
+        .map(|t| t.name())
+        .collect::<Vec<_>>();
+    println!("{:<7}{:<20}{}", 0, "right-associative", assign.join(" "));
+}
+
+fn format_files(args: &[&str]) {
+    let (check, paths) = match args {
+        ["--check", paths @ ..] => (true, paths),
+        paths => (false, paths),
+    };
+    if paths.is_empty() {
+        eprintln!("{USAGE}");
+        std::process::exit(2);
+    }
+
+    let mut ok = true;
+    for path in paths {
+        ok &= format_file(path, check);
+    }
+    if !ok {
+        std::process::exit(1);
+    }
+}
+
+/// formats a file, or with `check` prints a diff if it isn't formatted. returns whether
+/// the file is formatted in the end
+fn format_file(path: &str, check: bool) -> bool {
+    if !std::path::Path::new(path).is_file() {
+        eprintln!("no such file: {path}");
+        return false;
+    }
+
+    let mut sources = SourceMap::default();
+
+    let mut parser = Parser::new(Source::new(path), &mut sources).with_syntax_tree();
+
+    let program = parser.parse_program();
+    let src = parser.src;
+    let errors = parser.errors;
+    let syntax = parser.syntax.unwrap();
+
+    // formatting code that doesn't parse could lose some of it
+    if !errors.is_empty() {
+        for i in errors {
+            i.into_report().display(&sources);
+        }
+        return false;
+    }
+
+    let content = &sources[src].content;
+    let formatted = format_program(&program, content, &syntax);
+    if formatted == *content {
+        return true;
+    }
+    if check {
+        println!("{path} is not formatted:");
+        print!("{}", diff::unified(content, &formatted, 3));
+        return false;
+    }
+    if let Err(e) = std::fs::write(path, formatted) {
+        eprintln!("couldn't write {path}: {e}");
+        return false;
+    }
+    true
+}
+
+fn run_file(path: &str) {
+    if !std::path::Path::new(path).is_file() {
+        eprintln!("no such file: {path}");
+        std::process::exit(2);
+    }
+
+    let ok = std::thread::scope(|s| {
+        std::thread::Builder::new()
+            .stack_size(INTERPRETER_STACK_SIZE)
+            .spawn_scoped(s, || run_source(Source::new(path)))
+            .unwrap()
+            .join()
+            .unwrap()
+    });
+    if !ok {
+        std::process::exit(1);
+    }
+}
+
+/// parses and runs `src`, reporting whatever goes wrong, and returns whether it succeeded
+fn run_source(src: Source) -> bool {
     let mut sources = SourceMap::default();
 
-    let mut parser = Parser::new(Source::new("glib.shid"), &mut sources);
+    let mut parser = Parser::new(src, &mut sources);
 
-    let ast = parser.parse_expr();
-    println!("{:#?}", ast);
+    let program = parser.parse_program();
+    let src = parser.src;
+    let errors = parser.errors;
 
-    for i in parser.errors {
+    for i in parser.warnings {
         i.into_report().display(&sources);
     }
+
+    if !errors.is_empty() {
+        for i in errors {
+            i.into_report().display(&sources);
+        }
+        return false;
+    }
+
+    let mut interpreter = Interpreter::new(src, &sources);
+    match interpreter.run_program(&program) {
+        Ok(Value::Unit) => true,
+        Ok(v) => {
+            println!("{}", v);
+            true
+        }
+        Err(e) => {
+            e.into_report().display(&sources);
+            false
+        }
+    }
 }
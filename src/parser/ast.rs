@@ -1,37 +1,223 @@
+use std::{
+    ops::{Deref, DerefMut},
+    rc::Rc,
+};
+
 use crate::sources::span::Spanned;
 
-use super::operators::{BinOp, UnaryOp};
+use super::operators::{AssignOp, BinOp, LogicalOp, UnaryOp};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     IntegerLiteral(u128),
     FloatLiteral(f64),
     StringLiteral(String),
+    BoolLiteral(bool),
 
     Ident(String),
 
     BinOp(Box<Spanned<Expr>>, BinOp, Box<Spanned<Expr>>),
+    /// `a && b` or `a || b`, where `b` is only evaluated if `a` doesn't decide the result
+    Logical(Box<Spanned<Expr>>, LogicalOp, Box<Spanned<Expr>>),
     UnaryOp(UnaryOp, Box<Spanned<Expr>>),
+    Assign(Box<Spanned<Expr>>, AssignOp, Box<Spanned<Expr>>),
 
     Block(Box<Spanned<Block>>),
+    If {
+        branches: Vec<(Spanned<Expr>, Spanned<Block>)>,
+        else_: Option<Box<Spanned<Block>>>,
+    },
+    Match {
+        scrutinee: Box<Spanned<Expr>>,
+        arms: Vec<MatchArm>,
+    },
+    While {
+        label: Option<Spanned<String>>,
+        cond: Box<Spanned<Expr>>,
+        body: Box<Spanned<Block>>,
+    },
+    For {
+        label: Option<Spanned<String>>,
+        pattern: Spanned<Pattern>,
+        iter: Box<Spanned<Expr>>,
+        body: Box<Spanned<Block>>,
+    },
+    Break {
+        label: Option<Spanned<String>>,
+        value: Option<Box<Spanned<Expr>>>,
+    },
+    Continue {
+        label: Option<Spanned<String>>,
+    },
+
+    Dbg(Box<Spanned<Expr>>),
+
+    /// the parameters and body are shared with the functions the lambda evaluates to
+    Lambda {
+        params: Rc<Vec<Spanned<Pattern>>>,
+        body: Rc<Spanned<Expr>>,
+    },
+    Call(Box<Spanned<Expr>>, Vec<Spanned<Expr>>),
+    Index(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    TupleField(Box<Spanned<Expr>>, Spanned<usize>),
+    Member(Box<Spanned<Expr>>, Spanned<String>),
 
     Array(Vec<Spanned<Expr>>),
+    /// `()` or `(a,)` and longer, where the empty tuple is the unit value
     Tuple(Vec<Spanned<Expr>>),
+    /// `a..b` or `a..=b`, where the start may be left out, and the end if it's exclusive
+    Range {
+        start: Option<Box<Spanned<Expr>>>,
+        end: RangeEnd,
+    },
 
     Error,
 }
 
+impl Expr {
+    /// whether this expression denotes a location that can be assigned to
+    pub fn is_place(&self) -> bool {
+        match self {
+            Expr::Ident(_) => true,
+            Expr::Index(target, _) | Expr::TupleField(target, _) | Expr::Member(target, _) => {
+                target.is_place()
+            }
+            _ => false,
+        }
+    }
+    /// whether this expression ends in a block, and so needs no `;` to be used as a statement
+    pub fn is_block_like(&self) -> bool {
+        matches!(
+            self,
+            Expr::Block(_)
+                | Expr::If { .. }
+                | Expr::Match { .. }
+                | Expr::While { .. }
+                | Expr::For { .. }
+        )
+    }
+}
+
+/// the end of a range expression or pattern
+#[derive(Debug, Clone, PartialEq)]
+pub enum RangeEnd {
+    /// `a..`
+    Open,
+    /// `a..b`
+    Exclusive(Box<Spanned<Expr>>),
+    /// `a..=b`
+    Inclusive(Box<Spanned<Expr>>),
+}
+
+impl RangeEnd {
+    pub fn bound(&self) -> Option<&Spanned<Expr>> {
+        match self {
+            RangeEnd::Open => None,
+            RangeEnd::Exclusive(end) | RangeEnd::Inclusive(end) => Some(end),
+        }
+    }
+    pub fn is_inclusive(&self) -> bool {
+        matches!(self, RangeEnd::Inclusive(_))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchArm {
+    pub pattern: Spanned<Pattern>,
+    pub guard: Option<Spanned<Expr>>,
+    pub body: Spanned<Expr>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Stmt {
     Expr(Expr),
+    Let(Spanned<Pattern>, Spanned<Expr>),
+    Fn(FnDecl),
+}
+
+impl Stmt {
+    /// whether this statement ends in a block, and so needs no `;` to be followed by another
+    pub fn is_block_like(&self) -> bool {
+        match self {
+            Stmt::Expr(e) => e.is_block_like(),
+            Stmt::Let(..) => false,
+            Stmt::Fn(_) => true,
+        }
+    }
+}
+
+/// the parameters and body are shared with the functions the declaration defines
+#[derive(Debug, Clone, PartialEq)]
+pub struct FnDecl {
+    pub name: Spanned<String>,
+    pub params: Rc<Vec<Spanned<Pattern>>>,
+    pub body: Rc<Spanned<Block>>,
+}
+
+/// a whole source file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub items: Vec<Documented<Spanned<Item>>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    /// any statement, including a `fn` declaration
+    Stmt(Stmt),
+    /// `import "path";`
+    Import(Spanned<String>),
+}
+
+impl Item {
+    /// whether this item ends in a block, and so needs no `;` to be followed by another
+    pub fn is_block_like(&self) -> bool {
+        match self {
+            Item::Stmt(s) => s.is_block_like(),
+            Item::Import(_) => false,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Block {
-    pub normal: Vec<Spanned<Stmt>>,
-    pub ret: Option<Spanned<Stmt>>,
+    pub normal: Vec<Documented<Spanned<Stmt>>>,
+    pub ret: Option<Documented<Spanned<Stmt>>>,
+}
+
+/// a value along with the `///` doc comments written directly above it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Documented<T> {
+    pub docs: Vec<Spanned<String>>,
+    pub val: T,
 }
+impl<T> Deref for Documented<T> {
+    type Target = T;
 
-// pub enum Pattern {
-//     Variable,
-// }
+    fn deref(&self) -> &Self::Target {
+        &self.val
+    }
+}
+impl<T> DerefMut for Documented<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.val
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    Ident(String),
+    Wildcard,
+    /// a literal, possibly negated
+    Literal(Box<Spanned<Expr>>),
+    /// `a..b`, `a..=b`, `a..` or `..=b`
+    Range {
+        start: Option<Box<Spanned<Expr>>>,
+        end: RangeEnd,
+    },
+
+    Tuple(Vec<Spanned<Pattern>>),
+    Array(Vec<Spanned<Pattern>>),
+
+    /// `..`, only valid directly inside a tuple or array pattern
+    Rest,
+}
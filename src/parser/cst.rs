@@ -0,0 +1,376 @@
+use std::{fmt::Display, rc::Rc};
+
+use crate::sources::span::Span;
+
+use super::{
+    ast::{Expr, Pattern},
+    lexer::Token,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeKind {
+    Program,
+    Import,
+    FnDecl,
+    Let,
+    ExprStmt,
+    Block,
+    MatchArm,
+
+    Literal,
+    Name,
+    Paren,
+    Tuple,
+    Array,
+    BlockExpr,
+    If,
+    Match,
+    While,
+    For,
+    Break,
+    Continue,
+    Dbg,
+    Lambda,
+    Call,
+    Index,
+    TupleField,
+    Member,
+    BinOp,
+    Logical,
+    UnaryOp,
+    Assign,
+    Range,
+
+    IdentPat,
+    WildcardPat,
+    LiteralPat,
+    RangePat,
+    ParenPat,
+    TuplePat,
+    ArrayPat,
+    RestPat,
+
+    /// tokens skipped while recovering from an error, or an expression that is missing
+    Error,
+}
+
+impl NodeKind {
+    pub fn of_expr(expr: &Expr) -> Self {
+        match expr {
+            Expr::IntegerLiteral(_)
+            | Expr::FloatLiteral(_)
+            | Expr::StringLiteral(_)
+            | Expr::BoolLiteral(_) => Self::Literal,
+            Expr::Ident(_) => Self::Name,
+            Expr::BinOp(..) => Self::BinOp,
+            Expr::Logical(..) => Self::Logical,
+            Expr::UnaryOp(..) => Self::UnaryOp,
+            Expr::Assign(..) => Self::Assign,
+            Expr::Block(_) => Self::BlockExpr,
+            Expr::If { .. } => Self::If,
+            Expr::Match { .. } => Self::Match,
+            Expr::While { .. } => Self::While,
+            Expr::For { .. } => Self::For,
+            Expr::Break { .. } => Self::Break,
+            Expr::Continue { .. } => Self::Continue,
+            Expr::Dbg(_) => Self::Dbg,
+            Expr::Lambda { .. } => Self::Lambda,
+            Expr::Call(..) => Self::Call,
+            Expr::Index(..) => Self::Index,
+            Expr::TupleField(..) => Self::TupleField,
+            Expr::Member(..) => Self::Member,
+            Expr::Array(_) => Self::Array,
+            Expr::Tuple(_) => Self::Tuple,
+            Expr::Range { .. } => Self::Range,
+            Expr::Error => Self::Error,
+        }
+    }
+    pub fn of_pattern(pattern: &Pattern) -> Self {
+        match pattern {
+            Pattern::Ident(_) => Self::IdentPat,
+            Pattern::Wildcard => Self::WildcardPat,
+            Pattern::Literal(_) => Self::LiteralPat,
+            Pattern::Range { .. } => Self::RangePat,
+            Pattern::Tuple(_) => Self::TuplePat,
+            Pattern::Array(_) => Self::ArrayPat,
+            Pattern::Rest => Self::RestPat,
+        }
+    }
+}
+
+/// the text the lexer skips between tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Trivia {
+    Whitespace,
+    /// `// ...`, not including the newline
+    LineComment,
+    /// `/* ... */`, possibly nested or unterminated
+    BlockComment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    Token(Token),
+    Trivia(Trivia),
+}
+
+impl TokenKind {
+    #[allow(dead_code)]
+    pub fn is_trivia(self) -> bool {
+        matches!(self, Self::Trivia(_))
+    }
+}
+
+/// splits the text between two tokens into its whitespace and comments
+pub fn split_trivia(mut text: &str) -> Vec<(Trivia, &str)> {
+    let mut out = vec![];
+    while !text.is_empty() {
+        let (kind, len) = if text.starts_with("//") {
+            (Trivia::LineComment, text.find('\n').unwrap_or(text.len()))
+        } else if text.starts_with("/*") {
+            (Trivia::BlockComment, block_comment_len(text))
+        } else {
+            // the lexer skips nothing else, so this is whitespace
+            let len = text
+                .find(|c: char| !c.is_whitespace())
+                .unwrap_or(text.len())
+                .max(text.chars().next().unwrap().len_utf8());
+            (Trivia::Whitespace, len)
+        };
+        out.push((kind, &text[..len]));
+        text = &text[len..];
+    }
+    out
+}
+/// the length of the possibly nested block comment `text` starts with
+fn block_comment_len(text: &str) -> usize {
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < text.len() {
+        if text[i..].starts_with("/*") {
+            depth += 1;
+            i += 2;
+        } else if text[i..].starts_with("*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += text[i..].chars().next().unwrap().len_utf8();
+        }
+    }
+    text.len()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GreenToken {
+    pub kind: TokenKind,
+    pub text: Box<str>,
+}
+
+/// a node of the lossless syntax tree, holding only kinds and text so it doesn't depend
+/// on where in the source it is
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GreenNode {
+    pub kind: NodeKind,
+    pub children: Vec<GreenElement>,
+    /// the length of all the text in this node
+    pub len: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GreenElement {
+    Node(Rc<GreenNode>),
+    Token(GreenToken),
+}
+
+impl GreenElement {
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Node(n) => n.len,
+            Self::Token(t) => t.text.len(),
+        }
+    }
+}
+
+impl Display for GreenNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for child in &self.children {
+            match child {
+                GreenElement::Node(n) => write!(f, "{}", n)?,
+                GreenElement::Token(t) => write!(f, "{}", t.text)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// a position in the builder that children added after it can later be wrapped from
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Checkpoint(usize);
+
+/// builds a green tree bottom-up: tokens are pushed in order, and a node is made by
+/// wrapping everything pushed since a checkpoint
+#[derive(Debug, Default)]
+pub struct Builder {
+    children: Vec<GreenElement>,
+}
+
+impl Builder {
+    pub fn token(&mut self, kind: TokenKind, text: &str) {
+        self.children.push(GreenElement::Token(GreenToken {
+            kind,
+            text: text.into(),
+        }));
+    }
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.children.len())
+    }
+    pub fn wrap(&mut self, checkpoint: Checkpoint, kind: NodeKind) {
+        let children = self.children.split_off(checkpoint.0);
+        let len = children.iter().map(GreenElement::len).sum();
+        self.children.push(GreenElement::Node(Rc::new(GreenNode {
+            kind,
+            children,
+            len,
+        })));
+    }
+    /// wraps everything into a single root node
+    pub fn finish(mut self, kind: NodeKind) -> SyntaxNode {
+        self.wrap(Checkpoint(0), kind);
+        match self.children.pop() {
+            Some(GreenElement::Node(root)) => SyntaxNode::new_root(root),
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct NodeData {
+    green: Rc<GreenNode>,
+    offset: usize,
+    parent: Option<SyntaxNode>,
+}
+
+/// a node of the green tree at a position in the source, with a link to its parent
+#[derive(Debug, Clone)]
+pub struct SyntaxNode(Rc<NodeData>);
+
+/// a token of the green tree at a position in the source
+#[derive(Debug, Clone)]
+pub struct SyntaxToken {
+    parent: SyntaxNode,
+    /// the index of this token in its parent's children
+    index: usize,
+    offset: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    pub fn new_root(green: Rc<GreenNode>) -> Self {
+        Self(Rc::new(NodeData {
+            green,
+            offset: 0,
+            parent: None,
+        }))
+    }
+
+    // the formatter only reads tokens, the rest of the navigation is for the tools built on
+    // this later
+    #[allow(dead_code)]
+    pub fn green(&self) -> &Rc<GreenNode> {
+        &self.0.green
+    }
+    #[allow(dead_code)]
+    pub fn kind(&self) -> NodeKind {
+        self.0.green.kind
+    }
+    #[allow(dead_code)]
+    pub fn span(&self) -> Span {
+        Span::new(self.0.offset, self.0.offset + self.0.green.len)
+    }
+    #[allow(dead_code)]
+    pub fn parent(&self) -> Option<&SyntaxNode> {
+        self.0.parent.as_ref()
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = SyntaxElement> + '_ {
+        let mut offset = self.0.offset;
+        self.0
+            .green
+            .children
+            .iter()
+            .enumerate()
+            .map(move |(index, child)| {
+                let child_offset = offset;
+                offset += child.len();
+                match child {
+                    GreenElement::Node(green) => {
+                        SyntaxElement::Node(SyntaxNode(Rc::new(NodeData {
+                            green: green.clone(),
+                            offset: child_offset,
+                            parent: Some(self.clone()),
+                        })))
+                    }
+                    GreenElement::Token(_) => SyntaxElement::Token(SyntaxToken {
+                        parent: self.clone(),
+                        index,
+                        offset: child_offset,
+                    }),
+                }
+            })
+    }
+    #[allow(dead_code)]
+    pub fn child_nodes(&self) -> impl Iterator<Item = SyntaxNode> + '_ {
+        self.children().filter_map(|c| match c {
+            SyntaxElement::Node(n) => Some(n),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+    /// every token in this node in source order, trivia included
+    pub fn descendant_tokens(&self) -> Vec<SyntaxToken> {
+        let mut out = vec![];
+        for child in self.children() {
+            match child {
+                SyntaxElement::Node(n) => out.extend(n.descendant_tokens()),
+                SyntaxElement::Token(t) => out.push(t),
+            }
+        }
+        out
+    }
+}
+
+impl SyntaxToken {
+    fn green(&self) -> &GreenToken {
+        match &self.parent.0.green.children[self.index] {
+            GreenElement::Token(t) => t,
+            GreenElement::Node(_) => unreachable!(),
+        }
+    }
+
+    pub fn kind(&self) -> TokenKind {
+        self.green().kind
+    }
+    pub fn text(&self) -> &str {
+        &self.green().text
+    }
+    pub fn span(&self) -> Span {
+        Span::new(self.offset, self.offset + self.text().len())
+    }
+    #[allow(dead_code)]
+    pub fn parent(&self) -> &SyntaxNode {
+        &self.parent
+    }
+}
+
+impl Display for SyntaxNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.green)
+    }
+}
@@ -2,20 +2,151 @@ use crate::{error::make_error, sources::span::CodeArea};
 
 use super::lexer::Token;
 
+/// lists what was expected, like "`)`" or "one of `)`, `,`, expression"
+fn expected_desc(tokens: &[Token], node: Option<&str>) -> String {
+    let mut items = tokens
+        .iter()
+        .map(|t| format!("`{}`", t.name()))
+        .collect::<Vec<_>>();
+    items.extend(node.map(String::from));
+    match &items[..] {
+        [] => "something else".into(),
+        [one] => one.clone(),
+        _ => format!("one of {}", items.join(", ")),
+    }
+}
+
 make_error! {
     @kind: Error;
 
     ParserError {
 
-        @title: format!("Expected {}, found `{}`", expected, found.name());
+        @title: format!("Expected {}, found `{}`", expected_desc(&expected, node), found.name());
         @msgs: [
-            area => "Expected {}": expected;
+            area => "Expected {}": expected_desc(&expected, node);
         ];
         Expected {
-            expected: String,
+            /// every token that was checked for at this position
+            expected: Vec<Token>,
+            /// something else that could have started here, like "expression"
+            node: Option<&'static str>,
             found: Token,
             area: CodeArea,
         }
 
+        @title: "Unterminated block comment";
+        @msgs: [
+            area => "This comment is never closed";
+        ];
+        UnterminatedComment {
+            area: CodeArea,
+        }
+
+        @title: "Comparison operators cannot be chained";
+        @msgs: [
+            first => "Previous comparison here";
+            second => "Cannot be chained with this one, use parentheses to group the comparisons";
+        ];
+        ChainedComparison {
+            first: CodeArea,
+            second: CodeArea,
+        }
+
+        @title: "Ranges cannot be chained";
+        @msgs: [
+            first => "Previous range here";
+            second => "Cannot be chained with this one, use parentheses to group the ranges";
+        ];
+        ChainedRange {
+            first: CodeArea,
+            second: CodeArea,
+        }
+
+        @title: format!("`{}` outside of a loop", keyword);
+        @msgs: [
+            area => "Cannot `{}` outside of a loop": keyword;
+        ];
+        OutsideLoop {
+            keyword: &'static str,
+            area: CodeArea,
+        }
+
+        @title: format!("Undeclared label `'{}`", name);
+        @msgs: [
+            area => "No enclosing loop is labeled `'{}`": name;
+        ];
+        UndeclaredLabel {
+            name: String,
+            area: CodeArea,
+        }
+
+        @title: "Invalid assignment target";
+        @msgs: [
+            area => "Cannot assign to this expression";
+        ];
+        InvalidAssignTarget {
+            area: CodeArea,
+        }
+
+        @title: "Multiple rest patterns";
+        @msgs: [
+            first => "First `..` used here";
+            second => "`..` can only be used once per pattern";
+        ];
+        MultipleRestPatterns {
+            first: CodeArea,
+            second: CodeArea,
+        }
+
+        @title: "Invalid literal";
+        @msgs: [
+            area => "{}": reason;
+        ];
+        InvalidLiteral {
+            reason: String,
+            area: CodeArea,
+        }
+
+        @title: "Unterminated string literal";
+        @msgs: [
+            area => "This string is never closed";
+        ];
+        UnterminatedString {
+            area: CodeArea,
+        }
+
+        @title: format!("Invalid escape sequence `{}`", escape);
+        @msgs: [
+            area => "Unknown escape sequence";
+        ];
+        InvalidEscape {
+            escape: String,
+            area: CodeArea,
+        }
+
+        @title: "Invalid unicode escape";
+        @msgs: [
+            area => "Expected 1 to 6 hex digits in braces forming a valid code point, like {}": "\\u{1F600}";
+        ];
+        InvalidUnicodeEscape {
+            area: CodeArea,
+        }
+
+        @title: "Doc comment does not document anything";
+        @msgs: [
+            area => "Expected a statement after this doc comment";
+        ];
+        DanglingDocComment {
+            area: CodeArea,
+        }
+
+        @title: "Misplaced import";
+        @msgs: [
+            area => "Imports can only be at the top level of a file";
+        ];
+        MisplacedImport {
+            area: CodeArea,
+        }
+
     }
 }
@@ -0,0 +1,248 @@
+use std::rc::Rc;
+
+use crate::sources::span::{Span, Spanned};
+
+use super::ast::{
+    Block, Documented, Expr, FnDecl, Item, MatchArm, Pattern, Program, RangeEnd, Stmt,
+};
+
+/// rebuilds the ast, where each method defaults to folding the children of the node and putting
+/// it back together. the span of every `Spanned` is folded before what it wraps.
+///
+/// like the walks in `visit`, these match every variant without wildcards
+// only the tests fold the ast so far
+#[allow(dead_code)]
+pub trait Fold {
+    fn fold_program(&mut self, program: Program) -> Program {
+        walk_program(self, program)
+    }
+    fn fold_item(&mut self, item: Item, span: Span) -> Item {
+        walk_item(self, item, span)
+    }
+    fn fold_fn_decl(&mut self, decl: FnDecl) -> FnDecl {
+        walk_fn_decl(self, decl)
+    }
+    fn fold_stmt(&mut self, stmt: Stmt, span: Span) -> Stmt {
+        walk_stmt(self, stmt, span)
+    }
+    fn fold_block(&mut self, block: Block, _span: Span) -> Block {
+        walk_block(self, block)
+    }
+    fn fold_expr(&mut self, expr: Expr, _span: Span) -> Expr {
+        walk_expr(self, expr)
+    }
+    fn fold_arm(&mut self, arm: MatchArm) -> MatchArm {
+        walk_arm(self, arm)
+    }
+    fn fold_pattern(&mut self, pattern: Pattern, _span: Span) -> Pattern {
+        walk_pattern(self, pattern)
+    }
+    fn fold_span(&mut self, span: Span) -> Span {
+        span
+    }
+}
+
+fn spanned<F: Fold + ?Sized, T>(
+    f: &mut F,
+    node: Spanned<T>,
+    fold: fn(&mut F, T, Span) -> T,
+) -> Spanned<T> {
+    let span = f.fold_span(node.span);
+    Spanned {
+        val: fold(f, node.val, span),
+        span,
+    }
+}
+fn expr<F: Fold + ?Sized>(f: &mut F, expr: Spanned<Expr>) -> Spanned<Expr> {
+    spanned(f, expr, F::fold_expr)
+}
+/// folds in place, reusing the allocation
+fn boxed_expr<F: Fold + ?Sized>(f: &mut F, mut e: Box<Spanned<Expr>>) -> Box<Spanned<Expr>> {
+    *e = expr(f, *e);
+    e
+}
+fn block<F: Fold + ?Sized>(f: &mut F, block: Spanned<Block>) -> Spanned<Block> {
+    spanned(f, block, F::fold_block)
+}
+fn boxed_block<F: Fold + ?Sized>(f: &mut F, mut b: Box<Spanned<Block>>) -> Box<Spanned<Block>> {
+    *b = block(f, *b);
+    b
+}
+/// folds what `rc` points to, which is only cloned if it's shared
+fn shared<F: Fold + ?Sized, T: Clone>(f: &mut F, rc: Rc<T>, fold: fn(&mut F, T) -> T) -> Rc<T> {
+    Rc::new(fold(f, Rc::unwrap_or_clone(rc)))
+}
+fn pattern<F: Fold + ?Sized>(f: &mut F, pattern: Spanned<Pattern>) -> Spanned<Pattern> {
+    spanned(f, pattern, F::fold_pattern)
+}
+fn patterns<F: Fold + ?Sized>(f: &mut F, patterns: Vec<Spanned<Pattern>>) -> Vec<Spanned<Pattern>> {
+    patterns.into_iter().map(|p| pattern(f, p)).collect()
+}
+fn exprs<F: Fold + ?Sized>(f: &mut F, exprs: Vec<Spanned<Expr>>) -> Vec<Spanned<Expr>> {
+    exprs.into_iter().map(|e| expr(f, e)).collect()
+}
+fn range_end<F: Fold + ?Sized>(f: &mut F, end: RangeEnd) -> RangeEnd {
+    match end {
+        RangeEnd::Open => RangeEnd::Open,
+        RangeEnd::Exclusive(end) => RangeEnd::Exclusive(boxed_expr(f, end)),
+        RangeEnd::Inclusive(end) => RangeEnd::Inclusive(boxed_expr(f, end)),
+    }
+}
+/// folds just the span of a node that has nothing else to fold
+fn span_of<F: Fold + ?Sized, T>(f: &mut F, node: Spanned<T>) -> Spanned<T> {
+    Spanned {
+        span: f.fold_span(node.span),
+        val: node.val,
+    }
+}
+fn documented<F: Fold + ?Sized, T>(
+    f: &mut F,
+    node: Documented<Spanned<T>>,
+    fold: fn(&mut F, T, Span) -> T,
+) -> Documented<Spanned<T>> {
+    Documented {
+        docs: node.docs.into_iter().map(|doc| span_of(f, doc)).collect(),
+        val: spanned(f, node.val, fold),
+    }
+}
+
+pub fn walk_program<F: Fold + ?Sized>(f: &mut F, program: Program) -> Program {
+    let Program { items } = program;
+    Program {
+        items: items
+            .into_iter()
+            .map(|item| documented(f, item, F::fold_item))
+            .collect(),
+    }
+}
+pub fn walk_item<F: Fold + ?Sized>(f: &mut F, item: Item, span: Span) -> Item {
+    match item {
+        Item::Stmt(stmt) => Item::Stmt(f.fold_stmt(stmt, span)),
+        Item::Import(path) => Item::Import(span_of(f, path)),
+    }
+}
+pub fn walk_fn_decl<F: Fold + ?Sized>(f: &mut F, decl: FnDecl) -> FnDecl {
+    let FnDecl { name, params, body } = decl;
+    FnDecl {
+        name: span_of(f, name),
+        params: shared(f, params, patterns),
+        body: shared(f, body, block),
+    }
+}
+/// `span` is that of the statement, which is also the span of a statement expression
+pub fn walk_stmt<F: Fold + ?Sized>(f: &mut F, stmt: Stmt, span: Span) -> Stmt {
+    match stmt {
+        Stmt::Expr(expr) => Stmt::Expr(f.fold_expr(expr, span)),
+        Stmt::Let(pat, value) => Stmt::Let(pattern(f, pat), expr(f, value)),
+        Stmt::Fn(decl) => Stmt::Fn(f.fold_fn_decl(decl)),
+    }
+}
+pub fn walk_block<F: Fold + ?Sized>(f: &mut F, block: Block) -> Block {
+    let Block { normal, ret } = block;
+    Block {
+        normal: normal
+            .into_iter()
+            .map(|stmt| documented(f, stmt, F::fold_stmt))
+            .collect(),
+        ret: ret.map(|stmt| documented(f, stmt, F::fold_stmt)),
+    }
+}
+pub fn walk_expr<F: Fold + ?Sized>(f: &mut F, e: Expr) -> Expr {
+    match e {
+        Expr::IntegerLiteral(_)
+        | Expr::FloatLiteral(_)
+        | Expr::StringLiteral(_)
+        | Expr::BoolLiteral(_)
+        | Expr::Ident(_)
+        | Expr::Error => e,
+
+        Expr::BinOp(left, op, right) => Expr::BinOp(boxed_expr(f, left), op, boxed_expr(f, right)),
+        Expr::Logical(left, op, right) => {
+            Expr::Logical(boxed_expr(f, left), op, boxed_expr(f, right))
+        }
+        Expr::UnaryOp(op, operand) => Expr::UnaryOp(op, boxed_expr(f, operand)),
+        Expr::Assign(left, op, right) => {
+            Expr::Assign(boxed_expr(f, left), op, boxed_expr(f, right))
+        }
+
+        Expr::Block(b) => Expr::Block(boxed_block(f, b)),
+        Expr::If { branches, else_ } => Expr::If {
+            branches: branches
+                .into_iter()
+                .map(|(cond, body)| (expr(f, cond), block(f, body)))
+                .collect(),
+            else_: else_.map(|else_| boxed_block(f, else_)),
+        },
+        Expr::Match { scrutinee, arms } => Expr::Match {
+            scrutinee: boxed_expr(f, scrutinee),
+            arms: arms.into_iter().map(|arm| f.fold_arm(arm)).collect(),
+        },
+        Expr::While { label, cond, body } => Expr::While {
+            label: label.map(|label| span_of(f, label)),
+            cond: boxed_expr(f, cond),
+            body: boxed_block(f, body),
+        },
+        Expr::For {
+            label,
+            pattern: pat,
+            iter,
+            body,
+        } => Expr::For {
+            label: label.map(|label| span_of(f, label)),
+            pattern: pattern(f, pat),
+            iter: boxed_expr(f, iter),
+            body: boxed_block(f, body),
+        },
+        Expr::Break { label, value } => Expr::Break {
+            label: label.map(|label| span_of(f, label)),
+            value: value.map(|value| boxed_expr(f, value)),
+        },
+        Expr::Continue { label } => Expr::Continue {
+            label: label.map(|label| span_of(f, label)),
+        },
+
+        Expr::Dbg(value) => Expr::Dbg(boxed_expr(f, value)),
+
+        Expr::Lambda { params, body } => Expr::Lambda {
+            params: shared(f, params, patterns),
+            body: shared(f, body, expr),
+        },
+        Expr::Call(target, args) => Expr::Call(boxed_expr(f, target), exprs(f, args)),
+        Expr::Index(target, index) => Expr::Index(boxed_expr(f, target), boxed_expr(f, index)),
+        Expr::TupleField(target, index) => {
+            Expr::TupleField(boxed_expr(f, target), span_of(f, index))
+        }
+        Expr::Member(target, name) => Expr::Member(boxed_expr(f, target), span_of(f, name)),
+
+        Expr::Array(elems) => Expr::Array(exprs(f, elems)),
+        Expr::Tuple(elems) => Expr::Tuple(exprs(f, elems)),
+        Expr::Range { start, end } => Expr::Range {
+            start: start.map(|start| boxed_expr(f, start)),
+            end: range_end(f, end),
+        },
+    }
+}
+pub fn walk_arm<F: Fold + ?Sized>(f: &mut F, arm: MatchArm) -> MatchArm {
+    let MatchArm {
+        pattern: pat,
+        guard,
+        body,
+    } = arm;
+    MatchArm {
+        pattern: pattern(f, pat),
+        guard: guard.map(|guard| expr(f, guard)),
+        body: expr(f, body),
+    }
+}
+pub fn walk_pattern<F: Fold + ?Sized>(f: &mut F, pat: Pattern) -> Pattern {
+    match pat {
+        Pattern::Ident(_) | Pattern::Wildcard | Pattern::Rest => pat,
+        Pattern::Literal(lit) => Pattern::Literal(boxed_expr(f, lit)),
+        Pattern::Range { start, end } => Pattern::Range {
+            start: start.map(|start| boxed_expr(f, start)),
+            end: range_end(f, end),
+        },
+        Pattern::Tuple(elems) => Pattern::Tuple(patterns(f, elems)),
+        Pattern::Array(elems) => Pattern::Array(patterns(f, elems)),
+    }
+}
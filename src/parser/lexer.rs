@@ -1,18 +1,95 @@
-use logos::Logos;
+use logos::{FilterResult, Logos};
 
 use crate::sources::span::Span;
 
-#[derive(Logos, Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LexError {
+    #[default]
+    Unknown,
+    /// holds the byte offset of the opening `/*`
+    UnterminatedComment(usize),
+}
+
+/// skips a possibly nested block comment, called after lexing the opening `/*`
+fn block_comment(lexer: &mut logos::Lexer<'_, Token>) -> FilterResult<(), LexError> {
+    let start = lexer.span().start;
+    let mut depth = 1usize;
+    let mut chars = lexer.remainder().char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        match (c, chars.peek()) {
+            ('/', Some((_, '*'))) => {
+                chars.next();
+                depth += 1;
+            }
+            ('*', Some((_, '/'))) => {
+                chars.next();
+                depth -= 1;
+                if depth == 0 {
+                    lexer.bump(i + 2);
+                    return FilterResult::Skip;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    lexer.bump(lexer.remainder().len());
+    FilterResult::Error(LexError::UnterminatedComment(start))
+}
+
+/// finds the end of a string literal, called after lexing the opening `"`.
+/// unterminated strings still produce a token so the parser can report them
+fn string(lexer: &mut logos::Lexer<'_, Token>) {
+    let mut chars = lexer.remainder().char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '"' => {
+                lexer.bump(i + 1);
+                return;
+            }
+            _ => {}
+        }
+    }
+
+    lexer.bump(lexer.remainder().len());
+}
+
+/// finds the end of a raw string literal, called after lexing the opening `r#*"`
+fn raw_string(lexer: &mut logos::Lexer<'_, Token>) {
+    let hashes = lexer.slice().len() - 2;
+    let closing = format!("\"{}", "#".repeat(hashes));
+
+    match lexer.remainder().find(&closing) {
+        Some(i) => lexer.bump(i + closing.len()),
+        None => lexer.bump(lexer.remainder().len()),
+    }
+}
+
+#[derive(Logos, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[logos(error = LexError)]
 #[logos(skip r"[ \t\r\n\f]+")] // Ignore this regex pattern between tokens
+#[logos(skip r"//[^\n]*")]
 pub enum Token {
     #[regex("[a-zA-Z_][a-zA-Z_0-9]*")]
     Ident,
-    #[regex("[0-9]+")]
+    #[regex("'[a-zA-Z_][a-zA-Z_0-9]*")]
+    Label,
+    #[regex("[0-9][0-9_]*")]
+    #[regex("0[xob][0-9a-zA-Z_]*")]
     Integer,
-    #[regex("[0-9]+\\.([0-9]*)?")]
+    // a digit is required after the `.` so that `0..5` and `x.0.1` lex as expected
+    #[regex(r"[0-9][0-9_]*\.[0-9][0-9_]*([eE][+-]?[0-9_]+)?")]
+    #[regex(r"[0-9][0-9_]*[eE][+-]?[0-9_]+")]
     Float,
-    #[regex(r##""(?:[^"]|\\")*""##)]
+    #[token("\"", string)]
     String,
+    #[regex("r#*\"", raw_string)]
+    RawString,
 
     #[token("+")]
     Plus,
@@ -20,6 +97,8 @@ pub enum Token {
     Minus,
     #[token("*")]
     Asterisk,
+    #[token("**")]
+    Pow,
     #[token("/")]
     Div,
     #[token("%")]
@@ -37,6 +116,36 @@ pub enum Token {
     DivAssign,
     #[token("%=")]
     ModAssign,
+    #[token("&=")]
+    BitAndAssign,
+    #[token("|=")]
+    BitOrAssign,
+    #[token("^=")]
+    BitXorAssign,
+    #[token("<<=")]
+    ShiftLeftAssign,
+    #[token(">>=")]
+    ShiftRightAssign,
+
+    #[token("&&")]
+    And,
+    #[token("||")]
+    Or,
+    #[token("!")]
+    Not,
+
+    #[token("&")]
+    BitAnd,
+    #[token("|")]
+    BitOr,
+    #[token("^")]
+    BitXor,
+    #[token("~")]
+    BitNot,
+    #[token("<<")]
+    ShiftLeft,
+    #[token(">>")]
+    ShiftRight,
 
     #[token("==")]
     Eq,
@@ -73,6 +182,12 @@ pub enum Token {
 
     #[token("=>")]
     FatArrow,
+    #[token(".")]
+    Dot,
+    #[token("..")]
+    DotDot,
+    #[token("..=")]
+    DotDotEq,
 
     #[token("true")]
     True,
@@ -81,17 +196,34 @@ pub enum Token {
 
     #[token("let")]
     Let,
+    #[token("fn")]
+    Fn,
     #[token("if")]
     If,
     #[token("else")]
     Else,
+    #[token("match")]
+    Match,
     #[token("while")]
     While,
     #[token("for")]
     For,
+    #[token("in")]
+    In,
+    #[token("break")]
+    Break,
+    #[token("continue")]
+    Continue,
 
     #[token("dbg")]
     Dbg,
+    #[token("import")]
+    Import,
+
+    #[regex(r"///([^/\n][^\n]*)?", priority = 10)]
+    DocComment,
+    #[token("/*", block_comment)]
+    BlockComment,
 
     Unknown,
     Eof,
@@ -101,12 +233,15 @@ impl Token {
     pub fn name(self) -> &'static str {
         match self {
             Token::Ident => "identifier",
+            Token::Label => "label",
             Token::Integer => "int literal",
             Token::Float => "float literal",
             Token::String => "string literal",
+            Token::RawString => "raw string literal",
             Token::Plus => "+",
             Token::Minus => "-",
             Token::Asterisk => "*",
+            Token::Pow => "**",
             Token::Div => "/",
             Token::Mod => "%",
             Token::Assign => "=",
@@ -122,15 +257,34 @@ impl Token {
             Token::Semicolon => ";",
             Token::Colon => ":",
             Token::Let => "let",
+            Token::Fn => "fn",
             Token::PlusAssign => "+=",
             Token::MinusAssign => "-=",
             Token::MultAssign => "*=",
             Token::DivAssign => "/=",
             Token::ModAssign => "%=",
+            Token::BitAndAssign => "&=",
+            Token::BitOrAssign => "|=",
+            Token::BitXorAssign => "^=",
+            Token::ShiftLeftAssign => "<<=",
+            Token::ShiftRightAssign => ">>=",
             Token::If => "if",
             Token::Else => "else",
+            Token::Match => "match",
             Token::While => "while",
             Token::For => "for",
+            Token::In => "in",
+            Token::Break => "break",
+            Token::Continue => "continue",
+            Token::And => "&&",
+            Token::Or => "||",
+            Token::BitAnd => "&",
+            Token::BitOr => "|",
+            Token::BitXor => "^",
+            Token::BitNot => "~",
+            Token::ShiftLeft => "<<",
+            Token::ShiftRight => ">>",
+            Token::Not => "!",
             Token::Eq => "==",
             Token::NEq => "!=",
             Token::Lt => "<",
@@ -138,51 +292,114 @@ impl Token {
             Token::LtE => "<=",
             Token::GtE => ">=",
             Token::Dbg => "dbg",
+            Token::Import => "import",
             Token::True => "true",
             Token::False => "false",
             Token::FatArrow => "=>",
+            Token::Dot => ".",
+            Token::DotDot => "..",
+            Token::DotDotEq => "..=",
+            Token::DocComment => "doc comment",
+            Token::BlockComment => "block comment",
         }
     }
 }
 
-fn inner_next(lexer: &mut logos::Lexer<'_, Token>) -> Token {
-    lexer
-        .next()
-        .map(|v| v.unwrap_or(Token::Unknown))
-        .unwrap_or(Token::Eof)
-}
-
+/// the whole source lexed up front, read through a cursor
 #[derive(Debug, Clone)]
 pub struct Lexer<'a> {
-    inner: logos::Lexer<'a, Token>,
-    peeked: Option<Token>,
+    src: &'a str,
+    /// always ends with a single `Token::Eof`
+    tokens: Vec<(Token, Span)>,
+    /// index of the next token, one past the `Eof` once that has been consumed
+    pos: usize,
+    /// the opening `/*` of a block comment that is never closed, which swallows the rest of the input
+    pub unterminated_comment: Option<Span>,
 }
+
+/// a position in the token stream that the lexer can be rewound to
+#[derive(Debug, Clone, Copy)]
+pub struct Checkpoint(usize);
+
 impl<'a> Lexer<'a> {
     pub fn new(src: &'a str) -> Self {
+        let mut inner = Token::lexer(src);
+        let mut tokens = vec![];
+        let mut unterminated_comment = None;
+
+        loop {
+            let tok = match inner.next() {
+                Some(Ok(tok)) => tok,
+                Some(Err(LexError::UnterminatedComment(start))) => {
+                    unterminated_comment = Some(Span::new(start, start + 2));
+                    Token::Eof
+                }
+                Some(Err(LexError::Unknown)) => Token::Unknown,
+                None => Token::Eof,
+            };
+            if tok == Token::Eof {
+                tokens.push((tok, Span::new(src.len(), src.len())));
+                break;
+            }
+            let span = inner.span();
+            tokens.push((tok, Span::new(span.start, span.end)));
+        }
+
         Self {
-            inner: Token::lexer(src),
-            peeked: None,
+            src,
+            tokens,
+            pos: 0,
+            unterminated_comment,
         }
     }
-    pub fn peek(&mut self) -> Token {
-        if let Some(p) = self.peeked {
-            return p;
-        }
-        let peek = inner_next(&mut self.inner.clone());
-        self.peeked = Some(peek);
-        peek
+
+    fn get(&self, i: usize) -> (Token, Span) {
+        self.tokens[i.min(self.tokens.len() - 1)]
     }
-    pub fn span(&self) -> Span {
-        let span = self.inner.span();
-        Span::new(span.start, span.end)
+
+    pub fn next(&mut self) -> Token {
+        let tok = self.get(self.pos).0;
+        self.pos = (self.pos + 1).min(self.tokens.len());
+        tok
     }
-    pub fn slice(&self) -> &str {
-        self.inner.slice()
+    /// the `n`th upcoming token, `peek_nth(0)` being the next one
+    pub fn peek_nth(&self, n: usize) -> Token {
+        self.get(self.pos + n).0
     }
-    pub fn next(&mut self) -> Token {
-        match self.peeked.take() {
-            Some(v) => v,
-            None => inner_next(&mut self.inner),
+    pub fn peek(&self) -> Token {
+        self.peek_nth(0)
+    }
+    pub fn peek_span(&self) -> Span {
+        self.get(self.pos).1
+    }
+
+    /// the span of the last consumed token
+    pub fn span(&self) -> Span {
+        match self.pos {
+            0 => Span::new(0, 0),
+            pos => self.get(pos - 1).1,
         }
     }
+    /// the source text of the last consumed token
+    pub fn slice(&self) -> &'a str {
+        let span = self.span();
+        &self.src[span.start..span.end]
+    }
+
+    pub fn source(&self) -> &'a str {
+        self.src
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.pos)
+    }
+    pub fn rewind(&mut self, checkpoint: Checkpoint) {
+        self.pos = checkpoint.0;
+    }
+    /// the tokens consumed since `checkpoint`
+    pub fn since(&self, checkpoint: Checkpoint) -> impl Iterator<Item = Token> + '_ {
+        self.tokens[checkpoint.0..self.pos]
+            .iter()
+            .map(|&(tok, _)| tok)
+    }
 }
@@ -0,0 +1,128 @@
+use super::{ast::Expr, error::ParserError, lexer::Token, Parser};
+use crate::sources::span::Span;
+
+impl Parser<'_> {
+    /// decodes the current `Token::Integer` or `Token::Float`, reporting malformed
+    /// or out of range literals instead of panicking
+    pub(super) fn decode_number(&mut self, tok: Token) -> Expr {
+        let digits = self.slice().replace('_', "");
+
+        let result = if tok == Token::Float {
+            match digits.parse::<f64>() {
+                Ok(v) if v.is_finite() => Ok(Expr::FloatLiteral(v)),
+                Ok(_) => Err("Float literal is out of range".to_string()),
+                Err(_) => Err("Float literal has a malformed exponent".to_string()),
+            }
+        } else {
+            let (radix, base, digits) = match digits.get(..2) {
+                Some("0x") => (16, "hexadecimal", &digits[2..]),
+                Some("0o") => (8, "octal", &digits[2..]),
+                Some("0b") => (2, "binary", &digits[2..]),
+                _ => (10, "decimal", &digits[..]),
+            };
+            if digits.is_empty() {
+                Err(format!("Missing digits after the {} prefix", base))
+            } else if let Some(c) = digits.chars().find(|c| !c.is_digit(radix)) {
+                Err(format!("Invalid digit `{}` in {} literal", c, base))
+            } else {
+                u128::from_str_radix(digits, radix)
+                    .map(Expr::IntegerLiteral)
+                    .map_err(|_| "Integer literal does not fit in 128 bits".to_string())
+            }
+        };
+
+        result.unwrap_or_else(|reason| {
+            self.error(ParserError::InvalidLiteral {
+                reason,
+                area: self.area(self.span()),
+            });
+            Expr::Error
+        })
+    }
+
+    /// decodes the current `Token::String`, reporting bad escapes and a missing closing quote
+    pub(super) fn decode_string(&mut self) -> String {
+        let span = self.span();
+        let slice = self.slice().to_string();
+        let mut out = String::new();
+        let mut chars = slice.char_indices().skip(1).peekable();
+
+        let at = |from: usize, to: usize| Span::new(span.start + from, span.start + to);
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => return out,
+                '\\' => {
+                    let Some((j, e)) = chars.next() else {
+                        break;
+                    };
+                    let escape_end = j + e.len_utf8();
+                    match e {
+                        'n' => out.push('\n'),
+                        't' => out.push('\t'),
+                        'r' => out.push('\r'),
+                        '0' => out.push('\0'),
+                        '\\' => out.push('\\'),
+                        '"' => out.push('"'),
+                        'u' => {
+                            let mut end = escape_end;
+                            let mut digits = None;
+                            if chars.next_if(|(_, c)| *c == '{').is_some() {
+                                let mut hex = String::new();
+                                while let Some((k, c)) =
+                                    chars.next_if(|(_, c)| *c != '"' && *c != '\\')
+                                {
+                                    end = k + c.len_utf8();
+                                    if c == '}' {
+                                        digits = Some(hex);
+                                        break;
+                                    }
+                                    hex.push(c);
+                                }
+                            }
+                            match digits
+                                .filter(|hex| (1..=6).contains(&hex.len()))
+                                .and_then(|hex| u32::from_str_radix(&hex, 16).ok())
+                                .and_then(char::from_u32)
+                            {
+                                Some(c) => out.push(c),
+                                None => self.error(ParserError::InvalidUnicodeEscape {
+                                    area: self.area(at(i, end)),
+                                }),
+                            }
+                        }
+                        _ => self.error(ParserError::InvalidEscape {
+                            escape: slice[i..escape_end].to_string(),
+                            area: self.area(at(i, escape_end)),
+                        }),
+                    }
+                }
+                c => out.push(c),
+            }
+        }
+
+        self.error(ParserError::UnterminatedString {
+            area: self.area(at(0, 1)),
+        });
+        out
+    }
+
+    /// extracts the contents of the current `Token::RawString`, reporting a missing closing quote
+    pub(super) fn decode_raw_string(&mut self) -> String {
+        let span = self.span();
+        let slice = self.slice();
+        let hashes = slice[1..].chars().take_while(|c| *c == '#').count();
+        let opening = hashes + 2;
+        let closing = format!("\"{}", "#".repeat(hashes));
+
+        if slice.len() >= opening + closing.len() && slice.ends_with(&closing) {
+            slice[opening..slice.len() - closing.len()].to_string()
+        } else {
+            let out = slice[opening..].to_string();
+            self.error(ParserError::UnterminatedString {
+                area: self.area(Span::new(span.start, span.start + opening)),
+            });
+            out
+        }
+    }
+}
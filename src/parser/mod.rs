@@ -1,4 +1,7 @@
-use ast::{Block, Expr, Stmt};
+use std::rc::Rc;
+
+use ast::{Block, Documented, Expr, FnDecl, Item, MatchArm, Pattern, Program, RangeEnd, Stmt};
+use cst::{NodeKind, SyntaxNode, TokenKind};
 use error::ParserError;
 use lexer::{Lexer, Token};
 use warning::ParserWarning;
@@ -9,9 +12,17 @@ use crate::sources::{
 };
 
 pub mod ast;
+pub mod cst;
 pub mod error;
+pub mod fold;
 pub mod lexer;
+mod literals;
 pub mod operators;
+pub mod printer;
+#[cfg(test)]
+mod tests;
+pub mod visit;
+pub mod visit_mut;
 pub mod warning;
 
 pub struct Parser<'a> {
@@ -20,38 +31,206 @@ pub struct Parser<'a> {
     pub src: SourceKey,
     pub errors: Vec<ParserError>,
     pub warnings: Vec<ParserWarning>,
+    /// how many loops enclose the current position
+    loop_depth: usize,
+    /// labels of the enclosing loops that have one
+    loop_labels: Vec<String>,
+    /// the tokens checked for since the last one was consumed, for error messages
+    expected: Vec<Token>,
+    /// only there when building the syntax tree, see `with_syntax_tree`
+    cst: Option<cst::Builder>,
+    /// how much of the source has been pushed into `cst`
+    cst_pos: usize,
+    /// the lossless syntax tree of the source, once `parse_program` is done if it was asked for
+    pub syntax: Option<SyntaxNode>,
+    /// where the match guard being parsed starts, if in one
+    guard_start: Option<lexer::Checkpoint>,
+    /// set by an unexpected token until the parser resynchronizes, so that the
+    /// errors caused by being out of sync aren't reported
+    panicking: bool,
+}
+
+/// keywords that usually start a statement, where parsing can resume after an error
+fn starts_stmt(tok: Token) -> bool {
+    matches!(
+        tok,
+        Token::Let
+            | Token::Fn
+            | Token::Import
+            | Token::If
+            | Token::Match
+            | Token::While
+            | Token::For
+    )
 }
 
 impl<'a> Parser<'a> {
     pub fn new(src: Source, sources: &'a mut SourceMap) -> Self {
         let key = sources.insert(src);
+        let lexer = Lexer::new(&sources[key].content);
+        let errors = lexer
+            .unterminated_comment
+            .map(|span| ParserError::UnterminatedComment {
+                area: span.to_area(key),
+            })
+            .into_iter()
+            .collect();
         Self {
-            lexer: Lexer::new(&sources[key].content),
+            lexer,
             // prev: None,
             src: key,
-            errors: vec![],
+            errors,
             warnings: vec![],
+            loop_depth: 0,
+            loop_labels: vec![],
+            expected: vec![],
+            cst: None,
+            cst_pos: 0,
+            syntax: None,
+            guard_start: None,
+            panicking: false,
         }
     }
 
+    /// also builds the lossless syntax tree of the source into `syntax`, which is left out
+    /// by default as it makes parsing much slower
+    pub fn with_syntax_tree(mut self) -> Self {
+        self.cst = Some(cst::Builder::default());
+        self
+    }
+
     fn error(&mut self, error: ParserError) {
-        self.errors.push(error);
+        if !self.panicking {
+            // other errors leave the parser in a sensible state, so it can just carry on
+            self.panicking = matches!(error, ParserError::Expected { .. });
+            self.errors.push(error);
+        }
+    }
+    /// after an error inside brackets, skips past the `close` ending them if it comes before
+    /// any point `synchronize` would stop at, since everything after it is then back in sync
+    fn recover_to(&mut self, close: Token) {
+        let checkpoint = self.lexer.checkpoint();
+        let mut depth = 0usize;
+        let mut skipped = 0;
+        let found = loop {
+            let tok = self.lexer.next();
+            skipped += 1;
+            match tok {
+                _ if tok == close && depth == 0 => break true,
+                Token::Eof => break false,
+                Token::Semicolon if depth == 0 => break false,
+                _ if depth == 0 && starts_stmt(tok) => break false,
+                Token::OpenParen | Token::OpenSquare | Token::OpenCurly => depth += 1,
+                Token::CloseParen | Token::CloseSquare | Token::CloseCurly => {
+                    if depth == 0 {
+                        break false;
+                    }
+                    depth -= 1
+                }
+                _ => {}
+            }
+        };
+        self.lexer.rewind(checkpoint);
+        if found {
+            let checkpoint = self.checkpoint();
+            for _ in 1..skipped {
+                self.next_tok();
+            }
+            self.wrap(checkpoint, NodeKind::Error);
+            self.next_tok();
+            self.panicking = false;
+        }
+    }
+    /// skips to where parsing can resume after an unexpected token: just past a `;`, or
+    /// before a `}` or a keyword starting a statement. brackets are skipped as a whole
+    fn synchronize(&mut self) {
+        let checkpoint = self.checkpoint();
+        let mut depth = 0usize;
+        loop {
+            match self.peek_tok() {
+                // nothing left to resynchronize with, and anything else missing at the end
+                // of the file is just more of the same error
+                Token::Eof => {
+                    self.wrap(checkpoint, NodeKind::Error);
+                    return;
+                }
+                Token::Semicolon if depth == 0 => {
+                    self.wrap(checkpoint, NodeKind::Error);
+                    self.next_tok();
+                    break;
+                }
+                Token::CloseCurly if depth == 0 => {
+                    self.wrap(checkpoint, NodeKind::Error);
+                    break;
+                }
+                tok if depth == 0 && starts_stmt(tok) => {
+                    self.wrap(checkpoint, NodeKind::Error);
+                    break;
+                }
+                Token::OpenParen | Token::OpenSquare | Token::OpenCurly => depth += 1,
+                Token::CloseParen | Token::CloseSquare | Token::CloseCurly => {
+                    depth = depth.saturating_sub(1)
+                }
+                _ => {}
+            }
+            self.next_tok();
+        }
+        self.panicking = false;
     }
     fn warn(&mut self, warning: ParserWarning) {
         self.warnings.push(warning);
     }
 
     fn next_tok(&mut self) -> Token {
-        self.lexer.next()
+        self.expected.clear();
+        self.push_trivia();
+        let tok = self.lexer.next();
+        if let Some(cst) = &mut self.cst {
+            if tok != Token::Eof {
+                cst.token(TokenKind::Token(tok), self.lexer.slice());
+                self.cst_pos = self.lexer.span().end;
+            }
+        }
+        tok
+    }
+    /// pushes the whitespace and comments before the next token into the syntax tree
+    fn push_trivia(&mut self) {
+        let Some(cst) = &mut self.cst else {
+            return;
+        };
+        let end = self.lexer.peek_span().start;
+        if end > self.cst_pos {
+            let text = &self.lexer.source()[self.cst_pos..end];
+            for (kind, text) in cst::split_trivia(text) {
+                cst.token(TokenKind::Trivia(kind), text);
+            }
+            self.cst_pos = end;
+        }
+    }
+    /// a point that syntax nodes can be wrapped from, starting at the next token
+    fn checkpoint(&mut self) -> cst::Checkpoint {
+        self.push_trivia();
+        self.cst
+            .as_ref()
+            .map_or(cst::Checkpoint::default(), cst::Builder::checkpoint)
+    }
+    fn wrap(&mut self, checkpoint: cst::Checkpoint, kind: NodeKind) {
+        if let Some(cst) = &mut self.cst {
+            cst.wrap(checkpoint, kind);
+        }
     }
     fn peek_tok(&self) -> Token {
-        self.lexer.clone().next()
+        self.lexer.peek()
     }
+    #[allow(dead_code)]
     fn peek_toks<const N: usize>(&self) -> [Token; N] {
-        let mut l = self.lexer.clone();
-        std::array::from_fn(|_| l.next())
+        std::array::from_fn(|i| self.lexer.peek_nth(i))
     }
-    fn next_is(&self, tok: Token) -> bool {
+    /// checks for `tok`, remembering that it could have come next in case nothing expected does
+    fn next_is(&mut self, tok: Token) -> bool {
+        if !self.expected.contains(&tok) {
+            self.expected.push(tok);
+        }
         self.peek_tok() == tok
     }
     fn skip_tok(&mut self, tok: Token) -> bool {
@@ -63,20 +242,26 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn expect_tok_named(&mut self, tok: Token, name: &str) {
-        let peek = self.peek_tok();
-        if peek == tok {
-            self.next_tok();
-        } else {
-            self.error(ParserError::Expected {
-                expected: name.to_string(),
-                found: peek,
-                area: self.area(self.peek_span()),
-            });
+    /// reports that neither the tokens checked for here nor those in `also` came next,
+    /// nor whatever `node` describes
+    fn unexpected(&mut self, also: &[Token], node: Option<&'static str>) {
+        let mut expected = self.expected.clone();
+        for tok in also {
+            if !expected.contains(tok) {
+                expected.push(*tok);
+            }
         }
+        self.error(ParserError::Expected {
+            expected,
+            node,
+            found: self.peek_tok(),
+            area: self.area(self.peek_span()),
+        });
     }
     fn expect_tok(&mut self, tok: Token) {
-        self.expect_tok_named(tok, &format!("`{}`", tok.name()));
+        if !self.skip_tok(tok) {
+            self.unexpected(&[], None);
+        }
     }
 
     fn area(&self, span: Span) -> CodeArea {
@@ -91,9 +276,7 @@ impl<'a> Parser<'a> {
     }
 
     fn peek_span(&self) -> Span {
-        let mut l = self.lexer.clone();
-        l.next();
-        l.span()
+        self.lexer.peek_span()
     }
 
     /// meant to be called after passing the opening token
@@ -105,43 +288,73 @@ impl<'a> Parser<'a> {
             cb(self);
             if !self.skip_tok(delim) {
                 self.expect_tok(end);
+                if self.panicking {
+                    self.recover_to(end);
+                }
                 break;
             }
         }
     }
 
     pub fn parse_unit(&mut self) -> Spanned<Expr> {
+        let first = self.peek_tok();
+        let checkpoint = self.checkpoint();
+        let unit = self.parse_unit_inner();
+        let kind = match unit.val {
+            Expr::Tuple(_) | Expr::Lambda { .. } => NodeKind::of_expr(&unit.val),
+            // any other unit starting with `(` is just parenthesized
+            _ if first == Token::OpenParen => NodeKind::Paren,
+            _ => NodeKind::of_expr(&unit.val),
+        };
+        self.wrap(checkpoint, kind);
+        unit
+    }
+    fn parse_unit_inner(&mut self) -> Spanned<Expr> {
         let unary;
 
         match self.peek_tok() {
-            Token::Integer => {
+            t @ (Token::Integer | Token::Float) => {
                 self.next_tok();
-                Expr::IntegerLiteral(self.slice().parse().unwrap()).spanned(self.span())
+                self.decode_number(t).spanned(self.span())
             }
-            Token::Float => {
+            Token::String => {
+                self.next_tok();
+                Expr::StringLiteral(self.decode_string()).spanned(self.span())
+            }
+            Token::RawString => {
                 self.next_tok();
-                Expr::FloatLiteral(self.slice().parse().unwrap()).spanned(self.span())
+                Expr::StringLiteral(self.decode_raw_string()).spanned(self.span())
+            }
+            Token::True => {
+                self.next_tok();
+                Expr::BoolLiteral(true).spanned(self.span())
+            }
+            Token::False => {
+                self.next_tok();
+                Expr::BoolLiteral(false).spanned(self.span())
             }
             Token::Ident => {
                 self.next_tok();
                 Expr::Ident(self.slice().into()).spanned(self.span())
             }
+            Token::OpenParen if self.lambda_ahead() => {
+                self.next_tok();
+                let start = self.span();
+                let params = self.parse_params();
+                self.expect_tok(Token::FatArrow);
+                let body = self.in_fn_body(|slef| slef.parse_expr());
+                Expr::Lambda {
+                    params: Rc::new(params),
+                    body: Rc::new(body),
+                }
+                .spanned(start.extended(self.span()))
+            }
             Token::OpenParen => {
                 self.next_tok();
                 let start = self.span();
-                let inner = self.parse_expr();
-
-                if self.skip_tok(Token::Comma) {
-                    let mut v = vec![inner];
-
-                    self.list_parse(Token::Comma, Token::CloseParen, |slef| {
-                        v.push(slef.parse_expr());
-                    });
-
-                    Expr::Tuple(v).spanned(start.extended(self.span()))
-                } else {
-                    self.expect_tok(Token::CloseParen);
-                    inner.val.spanned(start.extended(self.span()))
+                match self.parse_parenthesized() {
+                    (tuple, true) => tuple,
+                    (inner, false) => inner.val.spanned(start.extended(self.span())),
                 }
             }
             Token::OpenSquare => {
@@ -157,10 +370,125 @@ impl<'a> Parser<'a> {
                 Expr::Array(v).spanned(start.extended(self.span()))
             }
             Token::OpenCurly => {
+                let block = self.parse_braced_block();
+                let span = block.span;
+                Expr::Block(Box::new(block)).spanned(span)
+            }
+            Token::If => {
+                self.next_tok();
+                let start = self.span();
+
+                let mut branches = vec![];
+                let mut else_ = None;
+                loop {
+                    let cond = self.parse_header();
+                    branches.push((cond, self.parse_braced_block()));
+
+                    if !self.skip_tok(Token::Else) {
+                        break;
+                    }
+                    if !self.skip_tok(Token::If) {
+                        else_ = Some(Box::new(self.parse_braced_block()));
+                        break;
+                    }
+                }
+
+                Expr::If { branches, else_ }.spanned(start.extended(self.span()))
+            }
+            Token::Dbg => {
+                self.next_tok();
+                let start = self.span();
+                // with parens right after it, `dbg(a)` is called like a function, so `dbg(a)[0]`
+                // indexes what it returns. otherwise it takes its operand like a unary operator,
+                // so `dbg a[0]` and `dbg 2 ** 3` debug all of `a[0]` and `2 ** 3`, while
+                // `dbg a + b` only debugs `a`
+                let val = if self.next_is(Token::OpenParen) {
+                    let checkpoint = self.checkpoint();
+                    self.next_tok();
+                    let (val, tuple) = self.parse_parenthesized();
+                    let kind = if tuple {
+                        NodeKind::of_expr(&val.val)
+                    } else {
+                        NodeKind::Paren
+                    };
+                    self.wrap(checkpoint, kind);
+                    val
+                } else {
+                    self.parse_prefix_operand(operators::unary_prec(Token::Minus).unwrap())
+                };
+                Expr::Dbg(Box::new(val)).spanned(start.extended(self.span()))
+            }
+            Token::Match => {
+                self.next_tok();
+                let start = self.span();
+                let scrutinee = self.parse_header();
+                self.expect_tok(Token::OpenCurly);
+
+                let mut arms = vec![];
+                // after an error before the arms there's no telling where they are, so
+                // they're left to the statement around the match to skip
+                while !self.panicking && !self.skip_tok(Token::CloseCurly) {
+                    if self.peek_tok() == Token::Eof {
+                        self.expect_tok(Token::CloseCurly);
+                        break;
+                    }
+                    // a malformed arm gets a single error and is skipped
+                    let (arm, ended) = self.parse_match_arm();
+                    if self.panicking {
+                        if !ended {
+                            self.skip_past_arm();
+                        }
+                        // like in `synchronize`, a missing `}` at the end of the file is
+                        // just more of the same error
+                        self.panicking = self.peek_tok() == Token::Eof;
+                    } else {
+                        arms.push(arm);
+                    }
+                }
+
+                Expr::Match {
+                    scrutinee: Box::new(scrutinee),
+                    arms,
+                }
+                .spanned(start.extended(self.span()))
+            }
+            Token::Label => {
+                self.next_tok();
+                let label = self.slice()[1..].to_string().spanned(self.span());
+                self.expect_tok(Token::Colon);
+                match self.peek_tok() {
+                    Token::While | Token::For => self.parse_loop(Some(label)),
+                    _ => {
+                        self.unexpected(&[Token::While, Token::For], None);
+                        Expr::Error.spanned(self.peek_span())
+                    }
+                }
+            }
+            Token::While | Token::For => self.parse_loop(None),
+            Token::Break => {
                 self.next_tok();
                 let start = self.span();
-                let block = self.parse_block();
-                Expr::Block(Box::new(block)).spanned(start.extended(self.span()))
+                let label = self.parse_jump_label("break", start);
+                let value = if matches!(
+                    self.peek_tok(),
+                    Token::Semicolon
+                        | Token::CloseCurly
+                        | Token::CloseParen
+                        | Token::CloseSquare
+                        | Token::Comma
+                        | Token::Eof
+                ) {
+                    None
+                } else {
+                    Some(Box::new(self.parse_expr()))
+                };
+                Expr::Break { label, value }.spanned(start.extended(self.span()))
+            }
+            Token::Continue => {
+                self.next_tok();
+                let start = self.span();
+                let label = self.parse_jump_label("continue", start);
+                Expr::Continue { label }.spanned(start.extended(self.span()))
             }
             unary_op
                 if {
@@ -170,57 +498,756 @@ impl<'a> Parser<'a> {
             {
                 self.next_tok();
                 let start = self.span();
-                let unary_prec = unary.unwrap();
-                let next_prec = operators::next_infix(unary_prec);
-                let val = match next_prec {
-                    Some(next_prec) => self.parse_op(next_prec),
-                    None => self.parse_value(),
-                };
+                let val = self.parse_prefix_operand(unary.unwrap());
 
                 Expr::UnaryOp(unary_op.to_unary_op().unwrap(), Box::new(val))
                     .spanned(start.extended(self.span()))
             }
-            t => {
-                self.error(ParserError::Expected {
-                    expected: "expression".into(),
-                    found: t,
-                    area: self.area(self.peek_span()),
-                });
+            _ => {
+                self.unexpected(&[], Some("expression"));
                 Expr::Error.spanned(self.peek_span())
             }
         }
     }
+    /// parses what's in parens after the `(`, which is either a tuple spanning the parens or a
+    /// single expression, along with whether it's a tuple
+    fn parse_parenthesized(&mut self) -> (Spanned<Expr>, bool) {
+        let start = self.span();
+        if self.skip_tok(Token::CloseParen) {
+            return (
+                Expr::Tuple(vec![]).spanned(start.extended(self.span())),
+                true,
+            );
+        }
+        let inner = self.parse_expr();
+
+        if self.skip_tok(Token::Comma) {
+            let mut v = vec![inner];
+
+            self.list_parse(Token::Comma, Token::CloseParen, |slef| {
+                v.push(slef.parse_expr());
+            });
+
+            (Expr::Tuple(v).spanned(start.extended(self.span())), true)
+        } else {
+            self.expect_tok(Token::CloseParen);
+            (inner, false)
+        }
+    }
+    /// parses the operand of a prefix operator with the precedence `prec`
+    fn parse_prefix_operand(&mut self, prec: usize) -> Spanned<Expr> {
+        match operators::next_infix(prec) {
+            Some(next_prec) => self.parse_op(next_prec),
+            None => self.parse_value(),
+        }
+    }
+    /// parses a match arm, along with whether the `,` or `}` ending it was reached
+    fn parse_match_arm(&mut self) -> (MatchArm, bool) {
+        let checkpoint = self.checkpoint();
+        let pattern = self.parse_pattern();
+        let guard = if self.skip_tok(Token::If) {
+            let outer = self.guard_start.replace(self.lexer.checkpoint());
+            let guard = self.parse_expr();
+            self.guard_start = outer;
+            Some(guard)
+        } else {
+            None
+        };
+        self.expect_tok(Token::FatArrow);
+        let body = self.parse_expr();
+
+        let ended = self.skip_tok(Token::Comma) || self.next_is(Token::CloseCurly);
+        if !ended && !body.is_block_like() {
+            self.expect_tok(Token::Comma);
+        }
+        self.wrap(checkpoint, NodeKind::MatchArm);
+        let arm = MatchArm {
+            pattern,
+            guard,
+            body,
+        };
+        (arm, ended)
+    }
+    /// skips to just after the `,` ending the current match arm, or to the `}` ending the match
+    fn skip_past_arm(&mut self) {
+        let checkpoint = self.checkpoint();
+        let mut depth = 0usize;
+        loop {
+            match self.peek_tok() {
+                Token::Eof => break,
+                Token::CloseCurly if depth == 0 => break,
+                Token::Comma if depth == 0 => {
+                    self.next_tok();
+                    break;
+                }
+                Token::OpenParen | Token::OpenSquare | Token::OpenCurly => depth += 1,
+                Token::CloseParen | Token::CloseSquare | Token::CloseCurly => {
+                    depth = depth.saturating_sub(1)
+                }
+                _ => {}
+            }
+            self.next_tok();
+        }
+        self.wrap(checkpoint, NodeKind::Error);
+    }
+    /// parses a `while` or `for` loop, with the label already parsed if there is one
+    fn parse_loop(&mut self, label: Option<Spanned<String>>) -> Spanned<Expr> {
+        let kind = self.next_tok();
+        let start = label.as_ref().map(|l| l.span).unwrap_or(self.span());
+
+        let header = if kind == Token::For {
+            let pattern = self.parse_pattern();
+            self.expect_tok(Token::In);
+            Some(pattern)
+        } else {
+            None
+        };
+        let cond = self.parse_header();
+
+        self.loop_depth += 1;
+        if let Some(label) = &label {
+            self.loop_labels.push(label.val.clone());
+        }
+        let body = Box::new(self.parse_braced_block());
+        if label.is_some() {
+            self.loop_labels.pop();
+        }
+        self.loop_depth -= 1;
+
+        let cond = Box::new(cond);
+        match header {
+            Some(pattern) => Expr::For {
+                label,
+                pattern,
+                iter: cond,
+                body,
+            },
+            None => Expr::While { label, cond, body },
+        }
+        .spanned(start.extended(self.span()))
+    }
+    /// parses the optional label after a `break` or `continue`, checking that the jump
+    /// actually has a loop to target
+    fn parse_jump_label(&mut self, keyword: &'static str, span: Span) -> Option<Spanned<String>> {
+        if self.loop_depth == 0 {
+            self.error(ParserError::OutsideLoop {
+                keyword,
+                area: self.area(span),
+            });
+        }
+        if !self.skip_tok(Token::Label) {
+            return None;
+        }
+        let label = self.slice()[1..].to_string().spanned(self.span());
+        if self.loop_depth > 0 && !self.loop_labels.contains(&label.val) {
+            self.error(ParserError::UndeclaredLabel {
+                name: label.val.clone(),
+                area: self.area(label.span),
+            });
+        }
+        Some(label)
+    }
+    /// checks whether the upcoming parenthesized list is followed by `=>`. outside of any
+    /// brackets in a match guard, that `=>` is the arm's, so `x if (x) => ...` is no lambda
+    fn lambda_ahead(&self) -> bool {
+        if let Some(guard) = self.guard_start {
+            let depth = self
+                .lexer
+                .since(guard)
+                .fold(0isize, |depth, tok| match tok {
+                    Token::OpenParen | Token::OpenSquare | Token::OpenCurly => depth + 1,
+                    Token::CloseParen | Token::CloseSquare | Token::CloseCurly => depth - 1,
+                    _ => depth,
+                });
+            if depth <= 0 {
+                return false;
+            }
+        }
+        let mut depth = 0usize;
+        let mut i = 0;
+        loop {
+            match self.lexer.peek_nth(i) {
+                Token::OpenParen | Token::OpenSquare | Token::OpenCurly => depth += 1,
+                Token::CloseParen | Token::CloseSquare | Token::CloseCurly => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return self.lexer.peek_nth(i + 1) == Token::FatArrow;
+                    }
+                }
+                Token::Eof => return false,
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+    /// parses function parameters, meant to be called after passing the opening paren
+    fn parse_params(&mut self) -> Vec<Spanned<Pattern>> {
+        let mut params = vec![];
+        self.list_parse(Token::Comma, Token::CloseParen, |slef| {
+            params.push(slef.parse_pattern());
+        });
+        params
+    }
+    /// parses the condition of an `if` or loop, or what a `match` matches on, which ends at a `{`
+    /// even inside a match guard
+    fn parse_header(&mut self) -> Spanned<Expr> {
+        let guard_start = self.guard_start.take();
+        let header = self.parse_expr();
+        self.guard_start = guard_start;
+        header
+    }
+    /// parses a function body, where `break` and `continue` cannot target any loop outside it
+    fn in_fn_body<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let depth = std::mem::take(&mut self.loop_depth);
+        let labels = std::mem::take(&mut self.loop_labels);
+        let out = f(self);
+        self.loop_depth = depth;
+        self.loop_labels = labels;
+        out
+    }
+
     pub fn parse_value(&mut self) -> Spanned<Expr> {
-        self.parse_unit()
+        let checkpoint = self.checkpoint();
+        let parenthesized = self.peek_tok() == Token::OpenParen;
+        let mut value = self.parse_unit();
+
+        // `if c {} (a, b)` is two statements, not a call, but `(if c {})(a, b)` is one
+        if value.is_block_like() && !parenthesized {
+            return value;
+        }
+
+        loop {
+            let value_span = value.span;
+            match self.peek_tok() {
+                Token::OpenParen => {
+                    self.next_tok();
+                    let mut args = vec![];
+                    self.list_parse(Token::Comma, Token::CloseParen, |slef| {
+                        args.push(slef.parse_expr());
+                    });
+                    let span = value.span.extended(self.span());
+                    value = Expr::Call(Box::new(value), args).spanned(span);
+                    self.wrap(checkpoint, NodeKind::Call);
+                }
+                Token::OpenSquare => {
+                    self.next_tok();
+                    let index = self.parse_expr();
+                    self.expect_tok(Token::CloseSquare);
+                    value = Expr::Index(Box::new(value), Box::new(index))
+                        .spanned(value_span.extended(self.span()));
+                    self.wrap(checkpoint, NodeKind::Index);
+                }
+                Token::Dot => {
+                    self.next_tok();
+                    value = self.parse_field(value);
+                    let kind = if value.span == value_span {
+                        NodeKind::Error
+                    } else {
+                        NodeKind::of_expr(&value.val)
+                    };
+                    self.wrap(checkpoint, kind);
+                }
+                _ => break value,
+            }
+        }
     }
+    /// parses what comes after the `.` in a field access
+    fn parse_field(&mut self, value: Spanned<Expr>) -> Spanned<Expr> {
+        match self.peek_tok() {
+            Token::Ident => {
+                self.next_tok();
+                let field = self.slice().to_string().spanned(self.span());
+                let span = value.span.extended(field.span);
+                Expr::Member(Box::new(value), field).spanned(span)
+            }
+            Token::Integer => {
+                self.next_tok();
+                let text = self.slice().to_string();
+                let index = self.tuple_index(&text, self.span());
+                let span = value.span.extended(index.span);
+                Expr::TupleField(Box::new(value), index).spanned(span)
+            }
+            // `t.0.1` lexes the indices as the float `0.1`
+            Token::Float => {
+                self.next_tok();
+                let span = self.span();
+                let slice = self.slice().to_string();
+                // a float without a `.`, like `1e5`, is just an invalid index
+                let Some((first, second)) = slice.split_once('.') else {
+                    let index = self.tuple_index(&slice, span);
+                    let span = value.span.extended(span);
+                    return Expr::TupleField(Box::new(value), index).spanned(span);
+                };
+                let first =
+                    self.tuple_index(first, Span::new(span.start, span.start + first.len()));
+                let second = self.tuple_index(second, Span::new(span.end - second.len(), span.end));
+
+                let inner_span = value.span.extended(first.span);
+                let inner = Expr::TupleField(Box::new(value), first).spanned(inner_span);
+                Expr::TupleField(Box::new(inner), second).spanned(inner_span.extended(span))
+            }
+            _ => {
+                self.unexpected(&[Token::Ident, Token::Integer], None);
+                value
+            }
+        }
+    }
+    fn tuple_index(&mut self, text: &str, span: Span) -> Spanned<usize> {
+        match text.parse() {
+            Ok(i) => i,
+            Err(_) => {
+                self.error(ParserError::InvalidLiteral {
+                    reason: "Tuple indices must be plain decimal integers".into(),
+                    area: self.area(span),
+                });
+                0
+            }
+        }
+        .spanned(span)
+    }
+
     pub fn parse_op(&mut self, prec: usize) -> Spanned<Expr> {
+        if operators::prec_type(prec) == operators::OpType::Range {
+            return self.parse_range(prec);
+        }
         let next_prec = operators::next_infix(prec);
 
+        let checkpoint = self.checkpoint();
         let mut left = match next_prec {
             Some(next_prec) => self.parse_op(next_prec),
             None => self.parse_value(),
         };
 
+        let mut prev_op: Option<Span> = None;
+
         while operators::is_infix_prec(self.peek_tok(), prec) {
             let op = self.next_tok();
+            let op_span = self.span();
 
-            let right = if operators::prec_type(prec) == operators::OpType::Left {
+            if let Some(prev_op) = prev_op {
+                if operators::prec_type(prec) == operators::OpType::NonAssoc {
+                    self.error(ParserError::ChainedComparison {
+                        first: self.area(prev_op),
+                        second: self.area(op_span),
+                    });
+                }
+            }
+            prev_op = Some(op_span);
+
+            let right = if operators::prec_type(prec) == operators::OpType::Right {
+                self.parse_op(prec)
+            } else {
                 match next_prec {
                     Some(next_prec) => self.parse_op(next_prec),
                     None => self.parse_value(),
                 }
-            } else {
-                self.parse_op(prec)
             };
             let new_span = left.span.extended(right.span);
-            left = Expr::BinOp(Box::new(left), op.to_bin_op().unwrap(), Box::new(right))
-                .spanned(new_span)
+            left = match op.to_logical_op() {
+                Some(op) => Expr::Logical(Box::new(left), op, Box::new(right)),
+                None => Expr::BinOp(Box::new(left), op.to_bin_op().unwrap(), Box::new(right)),
+            }
+            .spanned(new_span);
+            self.wrap(checkpoint, NodeKind::of_expr(&left.val));
         }
 
         left
     }
+    /// parses a range at precedence `prec`, where either bound may be left out
+    fn parse_range(&mut self, prec: usize) -> Spanned<Expr> {
+        let next_prec = operators::next_infix(prec);
+        let operand = |slef: &mut Self| match next_prec {
+            Some(next_prec) => slef.parse_op(next_prec),
+            None => slef.parse_value(),
+        };
+
+        let checkpoint = self.checkpoint();
+        let start = if operators::is_infix_prec(self.peek_tok(), prec) {
+            None
+        } else {
+            let start = operand(self);
+            if !operators::is_infix_prec(self.peek_tok(), prec) {
+                return start;
+            }
+            Some(start)
+        };
+
+        let inclusive = self.next_tok() == Token::DotDotEq;
+        let op_span = self.span();
+        let end = if self.range_end_ahead() {
+            let end = Box::new(operand(self));
+            match inclusive {
+                true => RangeEnd::Inclusive(end),
+                false => RangeEnd::Exclusive(end),
+            }
+        } else if inclusive {
+            self.unexpected(&[], Some("expression"));
+            let missing = Span::new(op_span.end, op_span.end);
+            RangeEnd::Inclusive(Box::new(Expr::Error.spanned(missing)))
+        } else {
+            RangeEnd::Open
+        };
+        let span = start
+            .as_ref()
+            .map_or(op_span, |s| s.span)
+            .extended(end.bound().map_or(op_span, |e| e.span));
+
+        if operators::is_infix_prec(self.peek_tok(), prec) {
+            self.next_tok();
+            self.error(ParserError::ChainedRange {
+                first: self.area(op_span),
+                second: self.area(self.span()),
+            });
+            if self.range_end_ahead() {
+                operand(self);
+            }
+        }
+
+        self.wrap(checkpoint, NodeKind::Range);
+        Expr::Range {
+            start: start.map(Box::new),
+            end,
+        }
+        .spanned(span)
+    }
+    /// whether the next token can start the end bound of a range.
+    /// `{` can't, so that `for i in 0.. {}` works
+    fn range_end_ahead(&mut self) -> bool {
+        matches!(
+            self.peek_tok(),
+            Token::Integer
+                | Token::Float
+                | Token::String
+                | Token::RawString
+                | Token::True
+                | Token::False
+                | Token::Ident
+                | Token::OpenParen
+                | Token::OpenSquare
+                | Token::If
+                | Token::Match
+                | Token::Dbg
+                | Token::Minus
+                | Token::Not
+                | Token::BitNot
+        )
+    }
+    /// assignments have the lowest precedence and are right-associative
     pub fn parse_expr(&mut self) -> Spanned<Expr> {
-        self.parse_op(0)
+        let checkpoint = self.checkpoint();
+        let left = self.parse_op(0);
+
+        match self.peek_tok().to_assign_op() {
+            Some(op) => {
+                self.next_tok();
+                if !left.is_place() {
+                    self.error(ParserError::InvalidAssignTarget {
+                        area: self.area(left.span),
+                    });
+                }
+                let right = self.parse_expr();
+                let span = left.span.extended(right.span);
+                self.wrap(checkpoint, NodeKind::Assign);
+                Expr::Assign(Box::new(left), op, Box::new(right)).spanned(span)
+            }
+            None => left,
+        }
+    }
+    pub fn parse_pattern(&mut self) -> Spanned<Pattern> {
+        let first = self.peek_tok();
+        let checkpoint = self.checkpoint();
+        let pattern = self.parse_pattern_inner();
+        let kind = match pattern.val {
+            Pattern::Tuple(_) => NodeKind::TuplePat,
+            _ if first == Token::OpenParen => NodeKind::ParenPat,
+            _ => NodeKind::of_pattern(&pattern.val),
+        };
+        self.wrap(checkpoint, kind);
+        pattern
+    }
+    fn parse_pattern_inner(&mut self) -> Spanned<Pattern> {
+        match self.peek_tok() {
+            Token::Ident => {
+                self.next_tok();
+                if self.slice() == "_" {
+                    Pattern::Wildcard.spanned(self.span())
+                } else {
+                    Pattern::Ident(self.slice().into()).spanned(self.span())
+                }
+            }
+            Token::Integer
+            | Token::Float
+            | Token::String
+            | Token::RawString
+            | Token::True
+            | Token::False
+            | Token::Minus => {
+                let start = self.parse_literal_pattern();
+                if self.next_is(Token::DotDot) || self.next_is(Token::DotDotEq) {
+                    let inclusive = self.next_tok() == Token::DotDotEq;
+                    let op_span = self.span();
+                    // `a..=` needs an end, but `a..` may leave it out
+                    let end = if inclusive {
+                        RangeEnd::Inclusive(Box::new(self.parse_literal_pattern()))
+                    } else if self.literal_pattern_ahead() {
+                        RangeEnd::Exclusive(Box::new(self.parse_literal_pattern()))
+                    } else {
+                        RangeEnd::Open
+                    };
+                    let span = start.span.extended(end.bound().map_or(op_span, |e| e.span));
+                    Pattern::Range {
+                        start: Some(Box::new(start)),
+                        end,
+                    }
+                    .spanned(span)
+                } else {
+                    let span = start.span;
+                    Pattern::Literal(Box::new(start)).spanned(span)
+                }
+            }
+            Token::DotDotEq => {
+                self.next_tok();
+                let start = self.span();
+                let end = self.parse_literal_pattern();
+                let span = start.extended(end.span);
+                Pattern::Range {
+                    start: None,
+                    end: RangeEnd::Inclusive(Box::new(end)),
+                }
+                .spanned(span)
+            }
+            Token::OpenParen => {
+                self.next_tok();
+                let start = self.span();
+
+                if self.skip_tok(Token::CloseParen) {
+                    return Pattern::Tuple(vec![]).spanned(start.extended(self.span()));
+                }
+                let first = self.parse_list_pattern_elem();
+                let comma = self.skip_tok(Token::Comma);
+                if !comma && first.val != Pattern::Rest {
+                    self.expect_tok(Token::CloseParen);
+                    return first.val.spanned(start.extended(self.span()));
+                }
+
+                let mut v = vec![first];
+                // `(..)` is a tuple even without a comma
+                if !comma {
+                    self.expect_tok(Token::CloseParen);
+                } else {
+                    self.list_parse(Token::Comma, Token::CloseParen, |slef| {
+                        v.push(slef.parse_list_pattern_elem());
+                    });
+                }
+                self.check_rest_patterns(&v);
+
+                Pattern::Tuple(v).spanned(start.extended(self.span()))
+            }
+            Token::OpenSquare => {
+                self.next_tok();
+                let start = self.span();
+
+                let mut v = vec![];
+                self.list_parse(Token::Comma, Token::CloseSquare, |slef| {
+                    v.push(slef.parse_list_pattern_elem());
+                });
+                self.check_rest_patterns(&v);
+
+                Pattern::Array(v).spanned(start.extended(self.span()))
+            }
+            _ => {
+                self.unexpected(&[], Some("pattern"));
+                Pattern::Wildcard.spanned(self.peek_span())
+            }
+        }
+    }
+    fn literal_pattern_ahead(&mut self) -> bool {
+        matches!(
+            self.peek_tok(),
+            Token::Integer
+                | Token::Float
+                | Token::String
+                | Token::RawString
+                | Token::True
+                | Token::False
+                | Token::Minus
+        )
+    }
+    /// parses a literal inside a pattern, allowing a leading `-` on numbers
+    fn parse_literal_pattern(&mut self) -> Spanned<Expr> {
+        let checkpoint = self.checkpoint();
+        if !self.skip_tok(Token::Minus) {
+            return self.parse_unit();
+        }
+        let start = self.span();
+        let out = match self.peek_tok() {
+            Token::Integer | Token::Float => {
+                let lit = self.parse_unit();
+                let span = start.extended(lit.span);
+                Expr::UnaryOp(operators::UnaryOp::Minus, Box::new(lit)).spanned(span)
+            }
+            _ => {
+                self.unexpected(&[Token::Integer, Token::Float], None);
+                Expr::Error.spanned(self.peek_span())
+            }
+        };
+        self.wrap(checkpoint, NodeKind::of_expr(&out.val));
+        out
+    }
+    /// parses an element of a tuple or array pattern, which may also be a `..` rest pattern
+    fn parse_list_pattern_elem(&mut self) -> Spanned<Pattern> {
+        let checkpoint = self.checkpoint();
+        if self.skip_tok(Token::DotDot) {
+            self.wrap(checkpoint, NodeKind::RestPat);
+            Pattern::Rest.spanned(self.span())
+        } else {
+            self.parse_pattern()
+        }
+    }
+    fn check_rest_patterns(&mut self, elems: &[Spanned<Pattern>]) {
+        let mut rests = elems.iter().filter(|p| p.val == Pattern::Rest);
+        if let Some(first) = rests.next() {
+            for second in rests {
+                self.error(ParserError::MultipleRestPatterns {
+                    first: self.area(first.span),
+                    second: self.area(second.span),
+                });
+            }
+        }
+    }
+
+    pub fn parse_stmt(&mut self) -> Spanned<Stmt> {
+        match self.peek_tok() {
+            Token::Let => {
+                let checkpoint = self.checkpoint();
+                self.next_tok();
+                let start = self.span();
+                let pattern = self.parse_pattern();
+                self.expect_tok(Token::Assign);
+                let value = self.parse_expr();
+                self.wrap(checkpoint, NodeKind::Let);
+                Stmt::Let(pattern, value).spanned(start.extended(self.span()))
+            }
+            Token::Fn => self.parse_fn_decl().map(Stmt::Fn),
+            // parsed anyway so that what comes after it is still read properly
+            Token::Import => {
+                let import = self.parse_item();
+                self.error(ParserError::MisplacedImport {
+                    area: self.area(import.span),
+                });
+                Stmt::Expr(Expr::Error).spanned(import.span)
+            }
+            _ => {
+                let checkpoint = self.checkpoint();
+                let expr = self.parse_expr();
+                self.wrap(checkpoint, NodeKind::ExprStmt);
+                expr.map(Stmt::Expr)
+            }
+        }
+    }
+    fn parse_fn_decl(&mut self) -> Spanned<FnDecl> {
+        let checkpoint = self.checkpoint();
+        self.expect_tok(Token::Fn);
+        let start = self.span();
+        self.expect_tok(Token::Ident);
+        let name = self.slice().to_string().spanned(self.span());
+        self.expect_tok(Token::OpenParen);
+        let params = self.parse_params();
+        let body = self.in_fn_body(|slef| slef.parse_braced_block());
+        self.wrap(checkpoint, NodeKind::FnDecl);
+        FnDecl {
+            name,
+            params: Rc::new(params),
+            body: Rc::new(body),
+        }
+        .spanned(start.extended(self.span()))
+    }
+    pub fn parse_item(&mut self) -> Spanned<Item> {
+        match self.peek_tok() {
+            Token::Import => {
+                let checkpoint = self.checkpoint();
+                self.next_tok();
+                let start = self.span();
+                let path = match self.peek_tok() {
+                    Token::String => {
+                        self.next_tok();
+                        self.decode_string()
+                    }
+                    Token::RawString => {
+                        self.next_tok();
+                        self.decode_raw_string()
+                    }
+                    _ => {
+                        self.unexpected(&[Token::String, Token::RawString], None);
+                        String::new()
+                    }
+                };
+                let path = path.spanned(self.span());
+                self.wrap(checkpoint, NodeKind::Import);
+                Item::Import(path).spanned(start.extended(self.span()))
+            }
+            _ => self.parse_stmt().map(Item::Stmt),
+        }
+    }
+    /// parses a whole file as a sequence of items
+    pub fn parse_program(&mut self) -> Program {
+        let mut program = Program { items: vec![] };
+
+        loop {
+            let docs = self.parse_docs();
+            if self.peek_tok() == Token::Eof {
+                if let (Some(first), Some(last)) = (docs.first(), docs.last()) {
+                    self.error(ParserError::DanglingDocComment {
+                        area: self.area(first.span.extended(last.span)),
+                    });
+                }
+                break;
+            }
+
+            let item = Documented {
+                docs,
+                val: self.parse_item(),
+            };
+            let block_like = item.is_block_like();
+            program.items.push(item);
+
+            // the last item may leave out its `;`
+            if !self.panicking
+                && !self.skip_tok(Token::Semicolon)
+                && !block_like
+                && !self.next_is(Token::Eof)
+            {
+                // `;` and end of file were both checked for
+                self.unexpected(&[], None);
+            }
+            if self.panicking {
+                self.synchronize();
+                // a stray `}` can't end anything at the top level
+                self.skip_tok(Token::CloseCurly);
+            }
+        }
+
+        self.push_trivia();
+        self.syntax = self.cst.take().map(|cst| cst.finish(NodeKind::Program));
+        program
+    }
+    /// parses any `///` doc comments, stripping the slashes and one leading space
+    pub fn parse_docs(&mut self) -> Vec<Spanned<String>> {
+        let mut docs = vec![];
+        while self.skip_tok(Token::DocComment) {
+            let text = &self.slice()[3..];
+            let text = text.strip_prefix(' ').unwrap_or(text);
+            docs.push(text.trim_end().to_string().spanned(self.span()));
+        }
+        docs
+    }
+    /// parses a block along with its opening brace
+    pub fn parse_braced_block(&mut self) -> Spanned<Block> {
+        let checkpoint = self.checkpoint();
+        self.expect_tok(Token::OpenCurly);
+        let block = self.parse_block();
+        self.wrap(checkpoint, NodeKind::Block);
+        block
     }
     /// meant to be called after passing the opening brace
     pub fn parse_block(&mut self) -> Spanned<Block> {
@@ -230,23 +1257,63 @@ impl<'a> Parser<'a> {
             ret: None,
         };
 
+        if self.skip_tok(Token::CloseCurly) {
+            return block.spanned(start.extended(self.span()));
+        }
+
         loop {
-            let expr = self.parse_expr();
-            let stmt = expr.map(Stmt::Expr);
-            if !self.skip_tok(Token::Semicolon) {
+            if self.peek_tok() == Token::Eof {
                 self.expect_tok(Token::CloseCurly);
-                block.ret = Some(stmt);
                 return block.spanned(start.extended(self.span()));
             }
+            let docs = self.parse_docs();
+            if let (Some(first), Some(last)) = (docs.first(), docs.last()) {
+                if self.skip_tok(Token::CloseCurly) {
+                    self.error(ParserError::DanglingDocComment {
+                        area: self.area(first.span.extended(last.span)),
+                    });
+                    return block.spanned(start.extended(self.span()));
+                }
+            }
+
+            let stmt = Documented {
+                docs,
+                val: self.parse_stmt(),
+            };
+            // block-like expressions can be followed by further statements without a `;`
+            let block_like = stmt.is_block_like();
+            let ends_stmt = block_like && !self.next_is(Token::CloseCurly);
+            if !self.panicking && !self.skip_tok(Token::Semicolon) && !ends_stmt {
+                if self.skip_tok(Token::CloseCurly) {
+                    if let Stmt::Expr(Expr::If {
+                        branches,
+                        else_: None,
+                    }) = &stmt.val.val
+                    {
+                        // only worth warning about if some branch actually produces a value
+                        if branches.iter().any(|(_, b)| b.ret.is_some()) {
+                            self.warn(ParserWarning::IfWithoutElse {
+                                area: self.area(stmt.span),
+                            });
+                        }
+                    }
+                    block.ret = Some(stmt);
+                    return block.spanned(start.extended(self.span()));
+                }
+                self.expect_tok(Token::CloseCurly);
+            }
+            if self.panicking {
+                self.synchronize();
+            }
             block.normal.push(stmt);
             if self.skip_tok(Token::CloseCurly) {
                 return block.spanned(start.extended(self.span()));
             }
         }
     }
+    #[allow(dead_code)]
     pub fn parse_cock(&mut self) -> Spanned<Block> {
-        self.expect_tok(Token::OpenCurly);
-        let out = self.parse_block();
+        let out = self.parse_braced_block();
         self.expect_tok(Token::Eof);
         out
     }
@@ -4,25 +4,32 @@ macro_rules! operators {
         Assign: [$($a_tok:ident),*];
         $(
             $( Left => [$($l_tok:ident),*] )?
+            $( Logical => [$($o_tok:ident),*] )?
             $( Right => [$($r_tok:ident),*] )?
+            $( NonAssoc => [$($n_tok:ident),*] )?
             $( Unary => [$($u_tok:ident),*] )?
+            $( Range => [$($g_tok:ident),*] )?
             ;
         )+
     ) => {
 
         use crate::parser::lexer::Token;
 
+        #[allow(dead_code)]
         #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
         pub enum Operator {
             Bin(BinOp),
+            Logical(LogicalOp),
             Unary(UnaryOp),
             Assign(AssignOp),
         }
 
+        #[allow(dead_code)]
         impl Operator {
             pub fn name(self) -> &'static str {
                 match self {
                     Self::Bin(b) => b.name(),
+                    Self::Logical(l) => l.name(),
                     Self::Unary(u) => u.name(),
                     Self::Assign(a) => a.name(),
                 }
@@ -40,8 +47,25 @@ macro_rules! operators {
         #[derive(Debug, Copy, Clone, PartialEq, Eq)]
         pub enum OpType {
             Left,
+            /// left-associative, but builds `Expr::Logical` instead of a binary op
+            Logical,
             Right,
+            NonAssoc,
             Unary,
+            /// non-associative, but builds `Expr::Range` instead of a binary op
+            Range,
+        }
+
+        impl OpType {
+            pub fn name(self) -> &'static str {
+                match self {
+                    Self::Left | Self::Logical => "left-associative",
+                    Self::Right => "right-associative",
+                    Self::NonAssoc => "non-associative",
+                    Self::Unary => "unary prefix",
+                    Self::Range => "non-associative",
+                }
+            }
         }
 
         #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -49,6 +73,13 @@ macro_rules! operators {
             $(
                 $($($l_tok,)+)?
                 $($($r_tok,)+)?
+                $($($n_tok,)+)?
+            )+
+        }
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum LogicalOp {
+            $(
+                $($($o_tok,)+)?
             )+
         }
         #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -60,13 +91,33 @@ macro_rules! operators {
 
         impl BinOp {
             pub fn name(self) -> &'static str {
+                self.token().name()
+            }
+            pub fn token(self) -> Token {
                 match self {
                     $(
                         $($(
-                            BinOp::$l_tok => Token::$l_tok.name(),
+                            BinOp::$l_tok => Token::$l_tok,
                         )+)?
                         $($(
-                            BinOp::$r_tok => Token::$r_tok.name(),
+                            BinOp::$r_tok => Token::$r_tok,
+                        )+)?
+                        $($(
+                            BinOp::$n_tok => Token::$n_tok,
+                        )+)?
+                    )+
+                }
+            }
+        }
+        impl LogicalOp {
+            pub fn name(self) -> &'static str {
+                self.token().name()
+            }
+            pub fn token(self) -> Token {
+                match self {
+                    $(
+                        $($(
+                            LogicalOp::$o_tok => Token::$o_tok,
                         )+)?
                     )+
                 }
@@ -74,10 +125,13 @@ macro_rules! operators {
         }
         impl UnaryOp {
             pub fn name(self) -> &'static str {
+                self.token().name()
+            }
+            pub fn token(self) -> Token {
                 match self {
                     $(
                         $($(
-                            UnaryOp::$u_tok => Token::$u_tok.name(),
+                            UnaryOp::$u_tok => Token::$u_tok,
                         )+)?
                     )+
                 }
@@ -99,6 +153,15 @@ macro_rules! operators {
                     $(
                         $($(Token::$l_tok => BinOp::$l_tok,)+)?
                         $($(Token::$r_tok => BinOp::$r_tok,)+)?
+                        $($(Token::$n_tok => BinOp::$n_tok,)+)?
+                    )+
+                    _ => return None,
+                })
+            }
+            pub fn to_logical_op(self) -> Option<LogicalOp> {
+                Some(match self {
+                    $(
+                        $($(Token::$o_tok => LogicalOp::$o_tok,)+)?
                     )+
                     _ => return None,
                 })
@@ -112,24 +175,45 @@ macro_rules! operators {
                 })
             }
             pub fn to_assign_op(self) -> Option<AssignOp> {
-                Some(match self {
+                match self {
                     $(
-                        Token::$a_tok => AssignOp::$a_tok,
+                        Token::$a_tok => Some(AssignOp::$a_tok),
                     )*
-                    _ => return None,
-                })
+                    _ => None,
+                }
             }
         }
 
         const OP_LIST: &[(OpType, &[Token])] = &[
             $(
                 $( (OpType::Left, &[$(Token::$l_tok),*]) )?
+                $( (OpType::Logical, &[$(Token::$o_tok),*]) )?
                 $( (OpType::Right, &[$(Token::$r_tok),*]) )?
+                $( (OpType::NonAssoc, &[$(Token::$n_tok),*]) )?
                 $( (OpType::Unary, &[$(Token::$u_tok),*]) )?
+                $( (OpType::Range, &[$(Token::$g_tok),*]) )?
             ),*
         ];
         pub const OP_COUNT: usize = OP_LIST.len();
 
+        /// assignment operators all share the lowest precedence, below every level in `OP_LIST`
+        pub const ASSIGN_LIST: &[Token] = &[$(Token::$a_tok),*];
+
+        /// every precedence level from lowest to highest, with the names of its operators
+        pub fn precedence_table() -> Vec<(OpType, Vec<&'static str>)> {
+            OP_LIST
+                .iter()
+                .map(|(typ, toks)| {
+                    let mut names: Vec<_> = toks.iter().map(|t| t.name()).collect();
+                    // `dbg` without parens takes its operand like the unary operators
+                    if *typ == OpType::Unary {
+                        names.push(Token::Dbg.name());
+                    }
+                    (*typ, names)
+                })
+                .collect()
+        }
+
         pub fn next_infix(prec: usize) -> Option<usize> {
             let mut next = prec + 1;
             while next < OP_COUNT {
@@ -149,6 +233,12 @@ macro_rules! operators {
             }
             return false
         }
+        /// the precedence level of an infix operator, including ranges
+        pub fn infix_prec(op: Token) -> Option<usize> {
+            OP_LIST
+                .iter()
+                .position(|(typ, toks)| *typ != OpType::Unary && toks.contains(&op))
+        }
         pub fn unary_prec(op: Token) -> Option<usize> {
             for (i, (typ, toks)) in OP_LIST.iter().enumerate() {
                 if *typ == OpType::Unary && toks.contains(&op) {
@@ -165,13 +255,44 @@ macro_rules! operators {
 }
 
 operators! {
-    Assign: [];
+    Assign: [
+        Assign, PlusAssign, MinusAssign, MultAssign, DivAssign, ModAssign,
+        BitAndAssign, BitOrAssign, BitXorAssign, ShiftLeftAssign, ShiftRightAssign
+    ];
 
     // lowest precedence
+    Range => [DotDot, DotDotEq];
+    Logical => [Or];
+    Logical => [And];
+    NonAssoc => [Eq, NEq, Lt, Gt, LtE, GtE];
+    // like rust, bitwise ops bind tighter than comparisons so `a & b == 0` needs no parens
+    Left => [BitOr];
+    Left => [BitXor];
+    Left => [BitAnd];
+    Left => [ShiftLeft, ShiftRight];
     Left => [Plus, Minus];
-    Unary => [Minus];
+    Unary => [Minus, Not, BitNot];
     Left => [Asterisk, Div, Mod];
-    // Right => [Pow];
+    Right => [Pow];
     // highest precedence
 
 }
+
+impl AssignOp {
+    /// the operator a compound assignment applies, `None` for plain `=`
+    pub fn bin_op(self) -> Option<BinOp> {
+        match self {
+            AssignOp::Assign => None,
+            AssignOp::PlusAssign => Some(BinOp::Plus),
+            AssignOp::MinusAssign => Some(BinOp::Minus),
+            AssignOp::MultAssign => Some(BinOp::Asterisk),
+            AssignOp::DivAssign => Some(BinOp::Div),
+            AssignOp::ModAssign => Some(BinOp::Mod),
+            AssignOp::BitAndAssign => Some(BinOp::BitAnd),
+            AssignOp::BitOrAssign => Some(BinOp::BitOr),
+            AssignOp::BitXorAssign => Some(BinOp::BitXor),
+            AssignOp::ShiftLeftAssign => Some(BinOp::ShiftLeft),
+            AssignOp::ShiftRightAssign => Some(BinOp::ShiftRight),
+        }
+    }
+}
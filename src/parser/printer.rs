@@ -0,0 +1,908 @@
+use std::{
+    fmt::{self, Display},
+    rc::Rc,
+};
+
+use crate::sources::span::{Span, Spanned};
+
+use super::{
+    ast::{Block, Documented, Expr, FnDecl, Item, MatchArm, Pattern, Program, Stmt},
+    cst::{SyntaxNode, TokenKind, Trivia},
+    lexer::{Lexer, Token},
+    operators::{self, OpType, UnaryOp, OP_COUNT},
+};
+
+/// lists are broken into one element per line when they don't fit in this width
+const MAX_WIDTH: usize = 100;
+const INDENT: &str = "    ";
+
+// how tightly an expression binds: assignment is 0, the levels of the precedence table follow
+// from 1, and prefix and postfix expressions bind tighter than any binary operator
+const ASSIGN: usize = 0;
+const PREFIX: usize = OP_COUNT + 1;
+const POSTFIX: usize = OP_COUNT + 2;
+
+fn infix_level(op: Token) -> usize {
+    operators::infix_prec(op).unwrap() + 1
+}
+/// how tightly the operand after an operator at `level` has to bind to need no parens
+fn next_level(level: usize) -> usize {
+    operators::next_infix(level - 1).map_or(PREFIX, |next| next + 1)
+}
+fn operand_levels(op: Token) -> (usize, usize) {
+    let level = infix_level(op);
+    match operators::prec_type(level - 1) {
+        OpType::Left | OpType::Logical => (level, next_level(level)),
+        OpType::Right => (next_level(level), level),
+        _ => (next_level(level), next_level(level)),
+    }
+}
+fn unary_operand_level(op: UnaryOp) -> usize {
+    next_level(operators::unary_prec(op.token()).unwrap() + 1)
+}
+fn range_level() -> usize {
+    infix_level(Token::DotDot)
+}
+
+fn prec(expr: &Expr) -> usize {
+    match expr {
+        Expr::Assign(..) => ASSIGN,
+        Expr::Range { .. } => range_level(),
+        Expr::BinOp(_, op, _) => infix_level(op.token()),
+        Expr::Logical(_, op, _) => infix_level(op.token()),
+        Expr::UnaryOp(..) | Expr::Lambda { .. } | Expr::Break { .. } => PREFIX,
+        _ => POSTFIX,
+    }
+}
+
+/// what comes right after an expression, which the end of a prefix expression might swallow
+#[derive(Debug, Clone, Copy)]
+enum Follow {
+    Nothing,
+    /// an infix operator binding at this level
+    Op(usize),
+    /// the `{` after an `if`, `match` or loop header
+    Header,
+    /// the `=>` after a match guard, which can't come after a lambda's parameters either
+    Guard,
+}
+
+fn needs_parens(expr: &Expr, min: usize, follow: Follow) -> bool {
+    if prec(expr) < min {
+        return true;
+    }
+    match (expr, follow) {
+        (Expr::UnaryOp(op, _), Follow::Op(level)) => level >= unary_operand_level(*op),
+        // the body of a lambda and the value of a `break` extend as far as they can
+        (Expr::Lambda { .. } | Expr::Break { .. }, Follow::Op(_)) => true,
+        (Expr::Break { .. }, Follow::Header | Follow::Guard) => true,
+        (Expr::Lambda { .. }, Follow::Guard) => true,
+        _ => false,
+    }
+}
+/// whether calls, indexing and fields need parens around what they apply to
+fn postfix_needs_parens(target: &Expr) -> bool {
+    // a block-like expression ends the value it starts, so `{ f } (x)` is two statements
+    prec(target) < POSTFIX || target.is_block_like()
+}
+
+/// the expression the printed `expr` starts with, or `None` if it starts with a paren
+fn leftmost(expr: &Expr, min: usize, follow: Follow) -> Option<&Expr> {
+    if needs_parens(expr, min, follow) {
+        return None;
+    }
+    match expr {
+        Expr::BinOp(left, op, _) => infix_leftmost(left, op.token()),
+        Expr::Logical(left, op, _) => infix_leftmost(left, op.token()),
+        Expr::Assign(left, ..) => leftmost(left, ASSIGN + 1, Follow::Op(ASSIGN)),
+        Expr::Range {
+            start: Some(start), ..
+        } => leftmost(start, next_level(range_level()), Follow::Op(range_level())),
+        Expr::Call(target, _)
+        | Expr::Index(target, _)
+        | Expr::TupleField(target, _)
+        | Expr::Member(target, _) => {
+            if postfix_needs_parens(target) {
+                None
+            } else {
+                leftmost(target, POSTFIX, Follow::Nothing)
+            }
+        }
+        _ => Some(expr),
+    }
+}
+fn infix_leftmost(left: &Expr, op: Token) -> Option<&Expr> {
+    leftmost(left, operand_levels(op).0, Follow::Op(infix_level(op)))
+}
+/// whether the printed `expr` could be read back as starting a statement or arm after a
+/// block-like one, which would continue that instead
+fn continues_previous(printed: &str) -> bool {
+    printed.starts_with('-') || printed.starts_with("..")
+}
+
+fn escape_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\0' => out.push_str("\\0"),
+            c if c.is_control() => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Comment<'a> {
+    span: Span,
+    text: &'a str,
+}
+
+/// prints the ast back into source, with only the parens that precedence requires
+pub struct Printer<'a> {
+    out: String,
+    indent: usize,
+    /// whether the indentation of the current line is still to be written
+    line_start: bool,
+    /// prints everything on a single line, writing a newline where that is impossible
+    flat: bool,
+    /// the source being formatted, to keep the exact text of literals and blank lines
+    src: Option<&'a str>,
+    comments: Rc<[Comment<'a>]>,
+    /// index of the first comment that hasn't been printed yet
+    next_comment: usize,
+    /// where in the source the last printed element or comment ends
+    last_pos: usize,
+}
+
+/// formats a whole source file, keeping its comments
+pub fn format_program(program: &Program, src: &str, syntax: &SyntaxNode) -> String {
+    let mut printer = Printer::new(Some(src));
+    printer.comments = syntax
+        .descendant_tokens()
+        .into_iter()
+        .filter(|tok| {
+            matches!(
+                tok.kind(),
+                TokenKind::Trivia(Trivia::LineComment | Trivia::BlockComment)
+            )
+        })
+        .map(|tok| Comment {
+            span: tok.span(),
+            text: &src[tok.span().start..tok.span().end],
+        })
+        .collect::<Vec<_>>()
+        .into();
+    printer.program(program);
+    printer.out
+}
+
+/// prints an ast that wasn't parsed from any source, so its spans mean nothing
+fn print_detached(
+    f: &mut fmt::Formatter<'_>,
+    print: impl FnOnce(&mut Printer, Span),
+) -> fmt::Result {
+    let mut printer = Printer::new(None);
+    print(&mut printer, Span::new(0, 0));
+    f.write_str(&printer.out)
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        print_detached(f, |p, span| p.expr(self, span, ASSIGN, Follow::Nothing))
+    }
+}
+impl Display for Stmt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        print_detached(f, |p, span| p.stmt(self, span))
+    }
+}
+impl Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        print_detached(f, |p, span| p.block(self, span))
+    }
+}
+
+impl<'a> Printer<'a> {
+    fn new(src: Option<&'a str>) -> Self {
+        Self {
+            out: String::new(),
+            indent: 0,
+            line_start: true,
+            flat: false,
+            src,
+            comments: Rc::new([]),
+            next_comment: 0,
+            last_pos: 0,
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        if self.line_start {
+            self.line_start = false;
+            for _ in 0..self.indent {
+                self.out.push_str(INDENT);
+            }
+        }
+        self.out.push_str(text);
+    }
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.line_start = true;
+    }
+    fn indented(&mut self, f: impl FnOnce(&mut Self)) {
+        self.indent += 1;
+        f(self);
+        self.indent -= 1;
+    }
+    /// writes any pending indentation, returning where the code that follows starts in the output
+    fn code_start(&mut self) -> usize {
+        self.write("");
+        self.out.len()
+    }
+    fn col(&self) -> usize {
+        if self.line_start {
+            return self.indent * INDENT.len();
+        }
+        let line = self.out.rfind('\n').map_or(0, |i| i + 1);
+        self.out[line..].chars().count()
+    }
+    fn fits(&self, text: &str) -> bool {
+        self.col() + text.chars().count() <= MAX_WIDTH
+    }
+    /// prints on a single line, if that's possible with every comment in `span` in its place
+    fn try_flat(&self, span: Span, f: impl FnOnce(&mut Printer<'a>)) -> Option<Printer<'a>> {
+        let mut printer = Printer {
+            flat: true,
+            comments: self.comments.clone(),
+            next_comment: self.next_comment,
+            last_pos: self.last_pos,
+            ..Printer::new(self.src)
+        };
+        f(&mut printer);
+        (!printer.out.contains('\n') && !printer.has_comments_in(span)).then_some(printer)
+    }
+
+    fn has_comments_in(&self, span: Span) -> bool {
+        self.comments[self.next_comment..]
+            .iter()
+            .take_while(|c| c.span.start < span.end)
+            .any(|c| c.span.start >= span.start)
+    }
+    /// whether the source has an empty line between the last printed thing and `pos`
+    fn blank_line_before(&self, pos: usize) -> bool {
+        let Some(src) = self.src else {
+            return false;
+        };
+        let mut lines = src[self.last_pos.min(pos)..pos].split('\n');
+        lines.next_back();
+        lines.skip(1).any(|line| line.trim().is_empty())
+    }
+    /// prints the comments before `pos` on their own lines, returning whether nothing was printed
+    /// since `first`
+    fn comments_before(&mut self, pos: usize, mut first: bool) -> bool {
+        while let Some(comment) = self.comments.get(self.next_comment).copied() {
+            if comment.span.start >= pos {
+                break;
+            }
+            if !first && self.blank_line_before(comment.span.start) {
+                self.newline();
+            }
+            self.write(comment.text.trim_end());
+            self.newline();
+            self.next_comment += 1;
+            self.last_pos = comment.span.end;
+            first = false;
+        }
+        first
+    }
+    /// prints the comments left on the line the last printed element ended on, along with any
+    /// that were inside it, up to the leading comments of the element starting at `next`
+    fn trailing_comments(&mut self, next: Option<usize>) {
+        let end = next.map_or(usize::MAX, |next| self.leading_start(next));
+        while let Some(comment) = self.comments.get(self.next_comment).copied() {
+            let src = self.src.unwrap();
+            if comment.span.start >= end
+                || comment.span.start >= self.last_pos
+                    && src[self.last_pos..comment.span.start].contains('\n')
+            {
+                break;
+            }
+            self.write(" ");
+            self.write(comment.text.trim_end());
+            if self.flat && comment.text.starts_with("//") {
+                self.newline();
+            }
+            self.next_comment += 1;
+            self.last_pos = self.last_pos.max(comment.span.end);
+        }
+    }
+    /// where the block comments right before `pos` on its line start, which go with the element
+    /// at `pos` rather than the one before it
+    fn leading_start(&self, pos: usize) -> usize {
+        let Some(src) = self.src else {
+            return pos;
+        };
+        let pending = &self.comments[self.next_comment..];
+        let before = pending.iter().take_while(|c| c.span.start < pos).count();
+        let mut start = pos;
+        for comment in pending[..before].iter().rev() {
+            let between = &src[comment.span.end..start];
+            if comment.text.starts_with("//")
+                || between.contains('\n')
+                || !between.trim().is_empty()
+            {
+                break;
+            }
+            start = comment.span.start;
+        }
+        start
+    }
+    /// prints the comments before `pos` on the line of the element there. a comment that had a
+    /// line of its own keeps it when printing flat, which makes that fail
+    fn leading_comments(&mut self, pos: usize) {
+        while let Some(comment) = self.comments.get(self.next_comment).copied() {
+            if comment.span.start >= pos {
+                break;
+            }
+            let src = self.src.unwrap();
+            if self.flat && src[self.last_pos..comment.span.start].contains('\n') {
+                self.newline();
+            }
+            self.write(comment.text.trim_end());
+            if comment.text.starts_with("//") {
+                self.newline();
+            } else {
+                self.write(" ");
+            }
+            self.next_comment += 1;
+            self.last_pos = comment.span.end;
+        }
+    }
+    /// gets ready to print an element starting at `pos` on its own line
+    fn start_line(&mut self, pos: usize, first: bool) {
+        let first = self.comments_before(pos, first);
+        if !first && self.blank_line_before(pos) {
+            self.newline();
+        }
+    }
+    /// the source text of the literal in `span`, when printing source that has it
+    fn source_text(&self, span: Span) -> Option<&'a str> {
+        // the span of a parenthesized literal includes the parens
+        let text = &self.src?[span.start..span.end];
+        let mut lexer = Lexer::new(text);
+        while let Token::OpenParen = lexer.next() {}
+        Some(lexer.slice())
+    }
+
+    fn program(&mut self, program: &Program) {
+        let mut first = true;
+        // where a `;` goes if the next item would otherwise continue the previous one
+        let mut separator = None;
+        for (i, item) in program.items.iter().enumerate() {
+            self.start_line(docs_start(item), first);
+            first = false;
+            self.docs(&item.docs);
+            let code = self.code_start();
+            match &item.val.val {
+                Item::Stmt(stmt) => self.stmt(stmt, item.span),
+                Item::Import(path) => {
+                    self.write("import ");
+                    let text = self
+                        .source_text(path.span)
+                        .map_or_else(|| escape_string(path), str::to_string);
+                    self.write(&text);
+                }
+            }
+            if let Some(at) = separator.take() {
+                if continues_previous(&self.out[code..]) {
+                    self.out.insert(at, ';');
+                }
+            }
+            // an expression at the very end is the value of the program, so it's left like the
+            // value of a block
+            let value =
+                i + 1 == program.items.len() && matches!(item.val.val, Item::Stmt(Stmt::Expr(_)));
+            if !item.is_block_like() && !value {
+                self.write(";");
+            } else if let Item::Stmt(Stmt::Expr(_)) = item.val.val {
+                separator = Some(self.out.len());
+            }
+            self.last_pos = item.span.end;
+            self.trailing_comments(None);
+            self.newline();
+        }
+        self.comments_before(usize::MAX, first);
+    }
+
+    fn docs(&mut self, docs: &[Spanned<String>]) {
+        for doc in docs {
+            self.write("///");
+            if !doc.is_empty() {
+                self.write(" ");
+                self.write(doc);
+            }
+            self.newline();
+        }
+    }
+
+    fn fn_decl(&mut self, decl: &FnDecl) {
+        self.write("fn ");
+        self.write(&decl.name);
+        let params = Span::new(decl.name.span.end, decl.body.span.start);
+        self.list(("(", ")"), &decl.params, params, false, Self::pattern);
+        self.write(" ");
+        self.block(&decl.body, decl.body.span);
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, span: Span) {
+        match stmt {
+            Stmt::Expr(expr) => self.expr(expr, span, ASSIGN, Follow::Nothing),
+            Stmt::Let(pattern, value) => {
+                self.write("let ");
+                self.pattern(pattern);
+                self.write(" = ");
+                self.operand(value, ASSIGN, Follow::Nothing);
+            }
+            Stmt::Fn(decl) => self.fn_decl(decl),
+        }
+    }
+
+    fn block(&mut self, block: &Block, span: Span) {
+        match self.inline_block(block, span) {
+            Some(text) => self.write(&text),
+            None => self.multiline_block(block, span),
+        }
+    }
+    /// the block on a single line, if it's just a short value
+    fn inline_block(&self, block: &Block, span: Span) -> Option<String> {
+        let Block { normal, ret } = block;
+        if !normal.is_empty() || self.has_comments_in(span) {
+            return None;
+        }
+        let text = match ret {
+            None => "{}".to_string(),
+            Some(Documented { docs, val }) if docs.is_empty() => match &val.val {
+                Stmt::Expr(expr) => {
+                    let expr = self.try_flat(val.span, |p| {
+                        p.expr(expr, val.span, ASSIGN, Follow::Nothing)
+                    })?;
+                    format!("{{ {} }}", expr.out)
+                }
+                _ => return None,
+            },
+            _ => return None,
+        };
+        (self.flat || self.fits(&text)).then_some(text)
+    }
+    fn multiline_block(&mut self, block: &Block, span: Span) {
+        let Block { normal, ret } = block;
+        if self.flat {
+            return self.newline();
+        }
+
+        self.write("{");
+        self.last_pos = span.start;
+        self.indented(|p| {
+            p.newline();
+            let mut first = true;
+            let mut separator = None;
+            let count = normal.len() + ret.is_some() as usize;
+            for (i, stmt) in normal.iter().chain(ret).enumerate() {
+                p.start_line(docs_start(stmt), first);
+                first = false;
+                p.docs(&stmt.docs);
+                let code = p.code_start();
+                p.stmt(&stmt.val.val, stmt.span);
+                if let Some(at) = separator.take() {
+                    if continues_previous(&p.out[code..]) {
+                        p.out.insert(at, ';');
+                    }
+                }
+                if i < normal.len() {
+                    // the last statement of a block is its value unless it's ended by a `;`
+                    if !stmt.is_block_like() || i + 1 == count {
+                        p.write(";");
+                    } else if let Stmt::Expr(_) = stmt.val.val {
+                        separator = Some(p.out.len());
+                    }
+                }
+                p.last_pos = stmt.span.end;
+                p.trailing_comments(None);
+                p.newline();
+            }
+            p.comments_before(span.end.saturating_sub(1), first);
+        });
+        self.write("}");
+    }
+
+    /// prints a bracketed list on one line if it fits, or with each element on its own line
+    fn list<T>(
+        &mut self,
+        (open, close): (&str, &str),
+        elems: &[Spanned<T>],
+        span: Span,
+        one_elem_comma: bool,
+        elem: impl Fn(&mut Printer<'a>, &Spanned<T>),
+    ) {
+        let next_start = |i: usize| elems.get(i + 1).map(|next| next.span.start);
+        let flat = |p: &mut Printer<'a>| {
+            p.write(open);
+            p.last_pos = span.start;
+            for (i, e) in elems.iter().enumerate() {
+                if i > 0 {
+                    p.write(", ");
+                }
+                p.leading_comments(e.span.start);
+                elem(p, e);
+                p.last_pos = e.span.end;
+                p.trailing_comments(next_start(i));
+            }
+            if one_elem_comma && elems.len() == 1 {
+                p.write(",");
+            }
+            p.write(close);
+        };
+        if self.flat {
+            return flat(self);
+        }
+        if let Some(flat) = self.try_flat(span, flat).filter(|p| self.fits(&p.out)) {
+            self.write(&flat.out);
+            self.next_comment = flat.next_comment;
+            self.last_pos = flat.last_pos;
+            return;
+        }
+
+        self.write(open);
+        self.last_pos = span.start;
+        self.indented(|p| {
+            p.newline();
+            let mut first = true;
+            for (i, e) in elems.iter().enumerate() {
+                p.start_line(p.leading_start(e.span.start), first);
+                first = false;
+                p.leading_comments(e.span.start);
+                elem(p, e);
+                p.write(",");
+                p.last_pos = e.span.end;
+                p.trailing_comments(next_start(i));
+                p.newline();
+            }
+            p.comments_before(span.end.saturating_sub(1), first);
+        });
+        self.write(close);
+    }
+
+    fn arms(&mut self, arms: &[MatchArm], end: usize) {
+        let mut first = true;
+        // where a `,` goes if the next arm would otherwise continue the previous one
+        let mut separator = None;
+        for arm in arms {
+            self.start_line(arm.pattern.span.start, first);
+            first = false;
+            let code = self.code_start();
+            self.pattern(&arm.pattern);
+            if let Some(at) = separator.take() {
+                if continues_previous(&self.out[code..]) {
+                    self.out.insert(at, ',');
+                }
+            }
+            if let Some(guard) = &arm.guard {
+                self.write(" if ");
+                self.operand(guard, ASSIGN, Follow::Guard);
+            }
+            self.write(" => ");
+            self.operand(&arm.body, ASSIGN, Follow::Nothing);
+            if arm.body.is_block_like() {
+                separator = Some(self.out.len());
+            } else {
+                self.write(",");
+            }
+            self.last_pos = arm.body.span.end;
+            self.trailing_comments(None);
+            self.newline();
+        }
+        self.comments_before(end, first);
+    }
+
+    fn label(&mut self, label: &Option<Spanned<String>>) {
+        if let Some(label) = label {
+            self.write(" '");
+            self.write(label);
+        }
+    }
+    fn loop_label(&mut self, label: &Option<Spanned<String>>) {
+        if let Some(label) = label {
+            self.write("'");
+            self.write(label);
+            self.write(": ");
+        }
+    }
+
+    fn operand(&mut self, expr: &Spanned<Expr>, min: usize, follow: Follow) {
+        self.expr(expr, expr.span, min, follow);
+    }
+    /// prints `expr` in parens if it doesn't bind at least as tightly as `min`, or if it would
+    /// swallow what follows it
+    fn expr(&mut self, expr: &Expr, span: Span, min: usize, follow: Follow) {
+        if needs_parens(expr, min, follow) {
+            self.parens(expr, span);
+        } else {
+            self.bare(expr, span, follow);
+        }
+    }
+    fn parens(&mut self, expr: &Expr, span: Span) {
+        self.write("(");
+        self.bare(expr, span, Follow::Nothing);
+        self.write(")");
+    }
+    fn infix(&mut self, left: &Spanned<Expr>, op: Token, right: &Spanned<Expr>, follow: Follow) {
+        let (left_min, right_min) = operand_levels(op);
+        self.operand(left, left_min, Follow::Op(infix_level(op)));
+        self.write(" ");
+        self.write(op.name());
+        self.write(" ");
+        self.operand(right, right_min, follow);
+    }
+    /// prints what a call, index or field applies to
+    fn target(&mut self, target: &Spanned<Expr>, is_tuple_field: bool) {
+        // `1.0` would be a float
+        let int = matches!(target.val, Expr::IntegerLiteral(_));
+        if postfix_needs_parens(target) || is_tuple_field && int {
+            self.parens(target, target.span);
+        } else {
+            self.bare(target, target.span, Follow::Nothing);
+        }
+    }
+
+    fn bare(&mut self, expr: &Expr, span: Span, follow: Follow) {
+        match expr {
+            Expr::IntegerLiteral(v) => {
+                let text = self
+                    .source_text(span)
+                    .map_or_else(|| v.to_string(), str::to_string);
+                self.write(&text);
+            }
+            Expr::FloatLiteral(v) => {
+                let text = self
+                    .source_text(span)
+                    .map_or_else(|| format!("{:?}", v), str::to_string);
+                self.write(&text);
+            }
+            Expr::StringLiteral(s) => {
+                let text = self
+                    .source_text(span)
+                    .map_or_else(|| escape_string(s), str::to_string);
+                self.write(&text);
+            }
+            Expr::BoolLiteral(v) => self.write(if *v { "true" } else { "false" }),
+            Expr::Ident(name) => self.write(name),
+
+            Expr::BinOp(left, op, right) => self.infix(left, op.token(), right, follow),
+            Expr::Logical(left, op, right) => self.infix(left, op.token(), right, follow),
+            Expr::UnaryOp(op, operand) => {
+                self.write(op.name());
+                self.operand(operand, unary_operand_level(*op), follow);
+            }
+            Expr::Assign(left, op, right) => {
+                self.operand(left, ASSIGN + 1, Follow::Op(ASSIGN));
+                self.write(" ");
+                self.write(op.name());
+                self.write(" ");
+                self.operand(right, ASSIGN, follow);
+            }
+            Expr::Range { start, end } => {
+                let min = next_level(range_level());
+                if let Some(start) = start {
+                    self.operand(start, min, Follow::Op(range_level()));
+                }
+                self.write(if end.is_inclusive() { "..=" } else { ".." });
+                if let Some(end) = end.bound() {
+                    // the end is only parsed if it starts like an expression that can't be
+                    // mistaken for what comes after a range, such as the body of a `for`
+                    let ambiguous = matches!(
+                        leftmost(end, min, follow),
+                        Some(
+                            Expr::Block(_)
+                                | Expr::While { .. }
+                                | Expr::For { .. }
+                                | Expr::Break { .. }
+                                | Expr::Continue { .. }
+                        )
+                    );
+                    if ambiguous || needs_parens(end, min, follow) {
+                        self.parens(end, end.span);
+                    } else {
+                        self.bare(end, end.span, follow);
+                    }
+                }
+            }
+
+            Expr::Block(block) => self.block(block, block.span),
+            Expr::If { branches, else_ } => {
+                // either the whole `if` fits on a line, or every branch gets its own lines
+                if !self.flat && !self.has_comments_in(span) {
+                    let inline = self.try_flat(span, |p| p.bare(expr, span, follow));
+                    if let Some(inline) = inline.filter(|p| self.fits(&p.out)) {
+                        return self.write(&inline.out);
+                    }
+                }
+                let block = |p: &mut Self, block: &Spanned<Block>| {
+                    if p.flat {
+                        p.block(block, block.span)
+                    } else {
+                        p.multiline_block(block, block.span)
+                    }
+                };
+                for (i, (cond, body)) in branches.iter().enumerate() {
+                    if i > 0 {
+                        self.write(" else ");
+                    }
+                    self.write("if ");
+                    self.operand(cond, ASSIGN, Follow::Header);
+                    self.write(" ");
+                    block(self, body);
+                }
+                if let Some(else_) = else_ {
+                    self.write(" else ");
+                    block(self, else_);
+                }
+            }
+            Expr::Match { scrutinee, arms } => {
+                self.write("match ");
+                self.operand(scrutinee, ASSIGN, Follow::Header);
+                if arms.is_empty() && !self.has_comments_in(span) {
+                    return self.write(" {}");
+                }
+                if self.flat {
+                    return self.newline();
+                }
+                self.write(" {");
+                self.last_pos = scrutinee.span.end;
+                self.indented(|p| {
+                    p.newline();
+                    p.arms(arms, span.end.saturating_sub(1));
+                });
+                self.write("}");
+            }
+            Expr::While { label, cond, body } => {
+                self.loop_label(label);
+                self.write("while ");
+                self.operand(cond, ASSIGN, Follow::Header);
+                self.write(" ");
+                self.block(body, body.span);
+            }
+            Expr::For {
+                label,
+                pattern,
+                iter,
+                body,
+            } => {
+                self.loop_label(label);
+                self.write("for ");
+                self.pattern(pattern);
+                self.write(" in ");
+                self.operand(iter, ASSIGN, Follow::Header);
+                self.write(" ");
+                self.block(body, body.span);
+            }
+            Expr::Break { label, value } => {
+                self.write("break");
+                self.label(label);
+                if let Some(value) = value {
+                    self.write(" ");
+                    // a labeled loop right after `break` would have its label taken as the break's
+                    let labeled_loop = matches!(
+                        leftmost(value, ASSIGN, follow),
+                        Some(Expr::While { label: Some(_), .. } | Expr::For { label: Some(_), .. })
+                    );
+                    if label.is_none() && labeled_loop {
+                        self.parens(value, value.span);
+                    } else {
+                        self.operand(value, ASSIGN, follow);
+                    }
+                }
+            }
+            Expr::Continue { label } => {
+                self.write("continue");
+                self.label(label);
+            }
+
+            Expr::Dbg(value) => {
+                self.write("dbg");
+                // a tuple already has the parens of the call
+                if let Expr::Tuple(_) = value.val {
+                    self.operand(value, POSTFIX, Follow::Nothing);
+                } else {
+                    self.write("(");
+                    self.operand(value, ASSIGN, Follow::Nothing);
+                    self.write(")");
+                }
+            }
+
+            Expr::Lambda { params, body } => {
+                let params_span = Span::new(span.start, body.span.start);
+                self.list(("(", ")"), params, params_span, false, Self::pattern);
+                self.write(" => ");
+                self.operand(body, ASSIGN, follow);
+            }
+            Expr::Call(target, args) => {
+                self.target(target, false);
+                let args_span = Span::new(target.span.end, span.end);
+                self.list(("(", ")"), args, args_span, false, |p, arg| {
+                    p.operand(arg, ASSIGN, Follow::Nothing)
+                });
+            }
+            Expr::Index(target, index) => {
+                self.target(target, false);
+                self.write("[");
+                self.operand(index, ASSIGN, Follow::Nothing);
+                self.write("]");
+            }
+            Expr::TupleField(target, index) => {
+                self.target(target, true);
+                self.write(".");
+                self.write(&index.to_string());
+            }
+            Expr::Member(target, name) => {
+                self.target(target, false);
+                self.write(".");
+                self.write(name);
+            }
+
+            Expr::Array(elems) => self.list(("[", "]"), elems, span, false, |p, e| {
+                p.operand(e, ASSIGN, Follow::Nothing)
+            }),
+            Expr::Tuple(elems) => self.list(("(", ")"), elems, span, true, |p, e| {
+                p.operand(e, ASSIGN, Follow::Nothing)
+            }),
+
+            Expr::Error => self.write("<error>"),
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Spanned<Pattern>) {
+        match &pattern.val {
+            Pattern::Ident(name) => self.write(name),
+            Pattern::Wildcard => self.write("_"),
+            Pattern::Literal(lit) => self.operand(lit, PREFIX, Follow::Nothing),
+            Pattern::Range { start, end } => {
+                if let Some(start) = start {
+                    self.operand(start, PREFIX, Follow::Nothing);
+                }
+                self.write(if end.is_inclusive() { "..=" } else { ".." });
+                if let Some(end) = end.bound() {
+                    self.operand(end, PREFIX, Follow::Nothing);
+                }
+            }
+            Pattern::Tuple(elems) => {
+                // `(..)` needs no comma to be a tuple
+                let comma = !matches!(
+                    elems[..],
+                    [Spanned {
+                        val: Pattern::Rest,
+                        ..
+                    }]
+                );
+                self.list(("(", ")"), elems, pattern.span, comma, Self::pattern);
+            }
+            Pattern::Array(elems) => {
+                self.list(("[", "]"), elems, pattern.span, false, Self::pattern)
+            }
+            Pattern::Rest => self.write(".."),
+        }
+    }
+}
+
+/// where a documented element starts, including its docs
+fn docs_start<T>(elem: &Documented<Spanned<T>>) -> usize {
+    elem.docs
+        .first()
+        .map_or(elem.span.start, |doc| doc.span.start)
+}
@@ -0,0 +1,1250 @@
+use std::rc::Rc;
+
+use crate::sources::{
+    span::{Span, Spannable, Spanned},
+    Source, SourceMap,
+};
+
+use super::{
+    ast::{Block, Documented, Expr, FnDecl, Item, MatchArm, Pattern, Program, RangeEnd, Stmt},
+    cst::{NodeKind, SyntaxNode, TokenKind, Trivia},
+    error::ParserError,
+    fold::{self, Fold},
+    lexer::{Lexer, Token},
+    operators::{AssignOp, BinOp, LogicalOp, UnaryOp},
+    printer::format_program,
+    visit::{self, Visit},
+    visit_mut::VisitMut,
+    warning::ParserWarning,
+    Parser,
+};
+
+fn parser<'a>(code: &str, sources: &'a mut SourceMap) -> Parser<'a> {
+    Parser::new(
+        Source {
+            path: "test.shid".into(),
+            content: code.into(),
+        },
+        sources,
+    )
+}
+
+fn parse(code: &str) -> (Program, Vec<ParserError>) {
+    let mut sources = SourceMap::default();
+    let mut parser = parser(code, &mut sources);
+    let program = parser.parse_program();
+    (program, parser.errors)
+}
+
+fn syntax(code: &str) -> SyntaxNode {
+    let mut sources = SourceMap::default();
+    let mut parser = parser(code, &mut sources).with_syntax_tree();
+    parser.parse_program();
+    parser.syntax.unwrap()
+}
+
+fn error_count(code: &str) -> usize {
+    parse(code).1.len()
+}
+
+#[test]
+fn valid_program_has_no_errors() {
+    let (program, errors) = parse(
+        "
+        fn add(a, b) { a + b }
+        let x = [1, 2, 3];
+        for i in 0..3 { x[i] += 1; }
+        match add(1, 2) { 3 => true, _ => false }
+        ",
+    );
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(program.items.len(), 4);
+    assert!(matches!(program.items[0].val.val, Item::Stmt(Stmt::Fn(_))));
+}
+
+#[test]
+fn three_independent_errors() {
+    let code = "
+        let a = 1 + ;
+        let b = (2, 3;
+        let c = 4 5;
+        let d = 6;
+    ";
+    let (program, errors) = parse(code);
+    assert_eq!(errors.len(), 3, "{:?}", errors);
+    // everything after the errors is still parsed
+    assert_eq!(program.items.len(), 4);
+}
+
+#[test]
+fn three_independent_errors_in_blocks() {
+    let code = "
+        fn f() {
+            let = 1;
+            2
+        }
+        fn g(a b) { a }
+        if true { ) } else { 3 }
+        f()
+    ";
+    assert_eq!(error_count(code), 3);
+}
+
+#[test]
+fn errors_in_nested_brackets_resync_after_them() {
+    let code = "
+        let a = foo(1, [2, 3 +], { 4 });
+        let b = [;
+        let c = 5;
+    ";
+    assert_eq!(error_count(code), 2);
+}
+
+#[test]
+fn stray_closing_tokens() {
+    assert_eq!(error_count("1; } 2; ) 3"), 2);
+}
+
+#[test]
+fn unterminated_block_stops_at_eof() {
+    assert_eq!(error_count("fn f() { let x = 1;"), 1);
+    assert_eq!(error_count("{ 1 +"), 1);
+}
+
+#[test]
+fn unclosed_brackets_after_an_error() {
+    assert_eq!(error_count("f(1 2 ("), 1);
+    assert_eq!(error_count("[1 2, { (3"), 1);
+    assert_eq!(error_count("let a = (1 2 [;\nlet b = 3;"), 1);
+}
+
+#[test]
+fn errors_before_match_arms() {
+    assert_eq!(error_count("let y = match ] 1;"), 1);
+    assert_eq!(error_count("let y = match x ] { 1 => 2 }; let z = 3;"), 1);
+    assert_eq!(error_count("match ) { 1 => 2, 3 => }"), 1);
+}
+
+#[test]
+fn imports_in_blocks() {
+    let (program, errors) = parse("{ import \"a\" }; fn f() { import \"b\"; 1 }");
+    assert!(
+        matches!(
+            errors[..],
+            [
+                ParserError::MisplacedImport { area: a },
+                ParserError::MisplacedImport { area: b },
+            ] if a.span == Span::new(2, 12) && b.span == Span::new(25, 35)
+        ),
+        "{errors:?}"
+    );
+    assert_eq!(program.items.len(), 2);
+}
+
+#[test]
+fn malformed_match_arms() {
+    let code = "
+        match x {
+            1 => ,
+            2 => 3,
+            => 4,
+            _ => 5,
+        }
+    ";
+    let (program, errors) = parse(code);
+    assert_eq!(errors.len(), 2);
+    let [item] = &program.items[..] else {
+        panic!("{:?}", program.items)
+    };
+    let Item::Stmt(Stmt::Expr(Expr::Match { arms, .. })) = &item.val.val else {
+        panic!("{:?}", item.val)
+    };
+    let bodies: Vec<_> = arms.iter().map(|arm| arm.body.to_string()).collect();
+    assert_eq!(bodies, ["3", "5"]);
+
+    // skipping a malformed arm stops at the end of the file, even inside brackets
+    assert_eq!(error_count("match 1 { . in { for"), 1);
+    assert_eq!(error_count("match 1 { 2 => [3 4 (5"), 1);
+}
+
+#[test]
+fn parenthesized_guards_arent_lambdas() {
+    let code = "
+        match n {
+            x if (x > 2) => 1,
+            x if x + (1) => 2,
+            x if ((y) => y)(x) => 3,
+            x if [(y) => y][0](x) => 4,
+            _ => 5,
+        }
+    ";
+    let (program, errors) = parse(code);
+    assert!(errors.is_empty(), "{:?}", errors);
+    let Item::Stmt(Stmt::Expr(Expr::Match { arms, .. })) = &program.items[0].val.val else {
+        panic!("{:?}", program.items)
+    };
+    let guards: Vec<_> = arms
+        .iter()
+        .map(|arm| arm.guard.as_ref().map(|guard| guard.to_string()))
+        .collect();
+    assert_eq!(
+        guards,
+        [
+            Some("x > 2".into()),
+            Some("x + 1".into()),
+            Some("((y) => y)(x)".into()),
+            Some("[(y) => y][0](x)".into()),
+            None,
+        ]
+    );
+}
+
+#[test]
+fn semantic_errors_dont_suppress_syntax_errors() {
+    // `break` outside a loop doesn't desync the parser, so the missing `)` is still reported
+    assert_eq!(error_count("break; (1, 2"), 2);
+}
+
+#[test]
+fn only_parenthesized_block_like_values_are_called() {
+    let (program, errors) = parse("if c { f } else { g } (1); (if c { f } else { g })(1)");
+    assert!(errors.is_empty(), "{:?}", errors);
+    let stmts: Vec<_> = program.items.iter().map(|item| &item.val.val).collect();
+    assert!(matches!(
+        stmts[..],
+        [
+            Item::Stmt(Stmt::Expr(Expr::If { .. })),
+            Item::Stmt(Stmt::Expr(Expr::IntegerLiteral(1))),
+            Item::Stmt(Stmt::Expr(Expr::Call(..))),
+        ]
+    ));
+}
+
+#[test]
+fn dbg_is_called_like_a_function() {
+    let (program, errors) = parse("dbg(a)[0] + dbg a[0]");
+    assert!(errors.is_empty(), "{:?}", errors);
+    let Item::Stmt(Stmt::Expr(Expr::BinOp(left, BinOp::Plus, right))) = &program.items[0].val.val
+    else {
+        panic!("{:?}", program.items)
+    };
+    let Expr::Index(target, _) = &left.val else {
+        panic!("{:?}", left)
+    };
+    // the parens aren't part of the debugged expression, so they aren't printed with it
+    let Expr::Dbg(value) = &target.val else {
+        panic!("{:?}", target)
+    };
+    assert_eq!(value.span, Span::new(4, 5));
+    assert!(matches!(&right.val, Expr::Dbg(value) if matches!(value.val, Expr::Index(..))));
+}
+
+#[test]
+fn dbg_operands() {
+    let cases = [
+        // with parens, `dbg` is called like a function
+        ("dbg(x)", "dbg(x)", "x"),
+        ("dbg (x, y)", "dbg(x, y)", "(x, y)"),
+        ("dbg() + 1", "dbg() + 1", "()"),
+        ("dbg (a) * b", "dbg(a) * b", "a"),
+        // otherwise it takes its operand like a unary operator
+        ("dbg 2 ** 3", "dbg(2 ** 3)", "2 ** 3"),
+        ("dbg -2 ** 2", "dbg(-2 ** 2)", "-2 ** 2"),
+        ("dbg a * b", "dbg(a * b)", "a * b"),
+        ("dbg a + b", "dbg(a) + b", "a"),
+    ];
+    for (code, printed, debugged) in cases {
+        let (program, errors) = parse(code);
+        assert!(errors.is_empty(), "{code}: {errors:?}");
+        let Item::Stmt(Stmt::Expr(expr)) = &program.items[0].val.val else {
+            panic!("{:?}", program.items)
+        };
+        assert_eq!(expr.to_string(), printed);
+
+        let mut dbg = expr;
+        while let Expr::BinOp(left, ..) = dbg {
+            dbg = &left.val;
+        }
+        let Expr::Dbg(value) = dbg else {
+            panic!("{code}: {dbg:?}")
+        };
+        assert_eq!(&code[value.span.start..value.span.end], debugged, "{code}");
+    }
+}
+
+#[test]
+fn invalid_tuple_indices() {
+    for code in ["t.1e5", "t.0x1", "t.0.1e5"] {
+        let errors = parse(code).1;
+        assert!(
+            matches!(errors[..], [ParserError::InvalidLiteral { .. }]),
+            "{code}: {errors:?}"
+        );
+    }
+}
+
+fn single_error(code: &str) -> ParserError {
+    match <[_; 1]>::try_from(parse(code).1) {
+        Ok([error]) => error,
+        Err(errors) => panic!("expected a single error in {code:?}, got {errors:?}"),
+    }
+}
+
+#[test]
+fn unterminated_comments() {
+    for (code, span) in [
+        ("1 /* never closed", 2..4),
+        ("1 /* a /* b */ c", 2..4),
+        ("/* a */ 2 /*", 10..12),
+    ] {
+        match single_error(code) {
+            ParserError::UnterminatedComment { area } => {
+                assert_eq!(area.span, span.into(), "{code}")
+            }
+            error => panic!("{code}: {error:?}"),
+        }
+    }
+}
+
+#[test]
+fn string_literals() {
+    for (code, value) in [
+        (r#""a\n\t\r\0\\\"b""#, "a\n\t\r\0\\\"b"),
+        (r#""\u{1F600}\u{e9}""#, "😀é"),
+        (r#"r"\q\n""#, r"\q\n"),
+        (r###"r##"a"#b"##"###, "a\"#b"),
+    ] {
+        assert_eq!(reparse(code), Expr::StringLiteral(value.into()), "{code}");
+    }
+}
+
+#[test]
+fn string_diagnostics() {
+    for (code, span, text) in [(r#""a\qb""#, 2..4, r"\q"), (r#""\x41""#, 1..3, r"\x")] {
+        match single_error(code) {
+            ParserError::InvalidEscape { escape, area } => {
+                assert_eq!((escape.as_str(), area.span), (text, span.into()), "{code}")
+            }
+            error => panic!("{code}: {error:?}"),
+        }
+    }
+    for (code, span) in [
+        (r#""\u{110000}""#, 1..11),
+        (r#""\u{}""#, 1..5),
+        (r#""\u{1234567}""#, 1..12),
+        (r#""\u12""#, 1..3),
+    ] {
+        match single_error(code) {
+            ParserError::InvalidUnicodeEscape { area } => {
+                assert_eq!(area.span, span.into(), "{code}")
+            }
+            error => panic!("{code}: {error:?}"),
+        }
+    }
+    for (code, span) in [
+        ("\"never closed", 0..1),
+        ("\"a\\", 0..1),
+        ("r#\"raw\"", 0..3),
+        ("r##\"raw\"#", 0..4),
+    ] {
+        match single_error(code) {
+            ParserError::UnterminatedString { area } => {
+                assert_eq!(area.span, span.into(), "{code}")
+            }
+            error => panic!("{code}: {error:?}"),
+        }
+    }
+}
+
+#[test]
+fn numeric_literals() {
+    for (code, value) in [
+        ("1_000_000", Expr::IntegerLiteral(1_000_000)),
+        ("0xff_FF", Expr::IntegerLiteral(0xffff)),
+        ("0o17", Expr::IntegerLiteral(0o17)),
+        ("0b1010", Expr::IntegerLiteral(0b1010)),
+        (
+            "340282366920938463463374607431768211455",
+            Expr::IntegerLiteral(u128::MAX),
+        ),
+        ("1e3", Expr::FloatLiteral(1000.0)),
+        ("1.5e-2", Expr::FloatLiteral(0.015)),
+    ] {
+        assert_eq!(reparse(code), value, "{code}");
+    }
+}
+
+#[test]
+fn numeric_literal_diagnostics() {
+    for (code, span, message) in [
+        ("0x", 0..2, "Missing digits after the hexadecimal prefix"),
+        ("1 + 0b102", 4..9, "Invalid digit `2` in binary literal"),
+        ("0o8", 0..3, "Invalid digit `8` in octal literal"),
+        (
+            "340282366920938463463374607431768211456",
+            0..39,
+            "Integer literal does not fit in 128 bits",
+        ),
+        ("[1e999]", 1..6, "Float literal is out of range"),
+    ] {
+        match single_error(code) {
+            ParserError::InvalidLiteral { reason, area } => {
+                assert_eq!(
+                    (reason.as_str(), area.span),
+                    (message, span.into()),
+                    "{code}"
+                )
+            }
+            error => panic!("{code}: {error:?}"),
+        }
+    }
+}
+
+#[test]
+fn invalid_assign_targets() {
+    for code in ["x = 1", "x.0 = 1", "a[0][1] += 1", "a.b = c = 2"] {
+        assert_eq!(error_count(code), 0, "{code}");
+    }
+    for (code, span) in [
+        ("1 = 2", 0..1),
+        ("f() = 3", 0..3),
+        ("a + b += 1", 0..5),
+        ("-x = 1", 0..2),
+        ("(a, b) = (1, 2)", 0..6),
+        ("[a, b] = [1, 2]", 0..6),
+        ("a = 1 = 2", 4..5),
+    ] {
+        match single_error(code) {
+            ParserError::InvalidAssignTarget { area } => {
+                assert_eq!(area.span, span.into(), "{code}")
+            }
+            error => panic!("{code}: {error:?}"),
+        }
+    }
+}
+
+#[test]
+fn chained_comparisons() {
+    for code in [
+        "(a < b) < c",
+        "a < (b < c)",
+        "a < b && b < c",
+        "a == (b < c)",
+    ] {
+        assert_eq!(error_count(code), 0, "{code}");
+    }
+    for (code, first, second) in [
+        ("a < b < c", 2..3, 6..7),
+        ("a == b != c", 2..4, 7..9),
+        ("1 < 2 == true", 2..3, 6..8),
+        ("x = a <= b > c", 6..8, 11..12),
+    ] {
+        match single_error(code) {
+            ParserError::ChainedComparison {
+                first: a,
+                second: b,
+            } => {
+                assert_eq!((a.span, b.span), (first.into(), second.into()), "{code}")
+            }
+            error => panic!("{code}: {error:?}"),
+        }
+    }
+}
+
+#[test]
+fn if_without_else_as_a_block_value() {
+    let warnings = |code: &str| {
+        let mut sources = SourceMap::default();
+        let mut parser = parser(code, &mut sources);
+        parser.parse_program();
+        assert!(parser.errors.is_empty(), "{code}: {:?}", parser.errors);
+        parser.warnings
+    };
+    for code in [
+        "{ if a { 1 } else { 2 } }",
+        "{ if a { f(); } }",
+        "{ if a { 1 }; 2 }",
+        "if a { 1 }",
+    ] {
+        assert!(warnings(code).is_empty(), "{code}");
+    }
+    for (code, span) in [
+        ("{ if a { 1 } }", 2..12),
+        ("let x = { 0; if a { 1 } else if b { 2 } };", 13..39),
+        ("f(() => { if a {} else if b { 2 } })", 10..33),
+    ] {
+        match &warnings(code)[..] {
+            [ParserWarning::IfWithoutElse { area }] => assert_eq!(area.span, span.into(), "{code}"),
+            warnings => panic!("{code}: {warnings:?}"),
+        }
+    }
+}
+
+#[test]
+fn jumps_outside_loops() {
+    for code in [
+        "'a: for i in 0..2 { while true { break 'a } }",
+        "while true { if x { continue } else { break } }",
+    ] {
+        assert_eq!(error_count(code), 0, "{code}");
+    }
+    for (code, span, jump) in [
+        ("break", 0..5, "break"),
+        ("fn f() { continue }", 9..17, "continue"),
+        ("while true { let f = () => { break }; }", 29..34, "break"),
+        ("'a: while true { fn f() { break 'a } }", 26..31, "break"),
+    ] {
+        match single_error(code) {
+            ParserError::OutsideLoop { keyword, area } => {
+                assert_eq!((keyword, area.span), (jump, span.into()), "{code}")
+            }
+            error => panic!("{code}: {error:?}"),
+        }
+    }
+    for (code, span, label) in [
+        ("while true { break 'a }", 19..21, "a"),
+        (
+            "'a: while true { for x in 0..1 { continue 'b } }",
+            42..44,
+            "b",
+        ),
+    ] {
+        match single_error(code) {
+            ParserError::UndeclaredLabel { name, area } => {
+                assert_eq!((name.as_str(), area.span), (label, span.into()), "{code}")
+            }
+            error => panic!("{code}: {error:?}"),
+        }
+    }
+}
+
+#[test]
+fn exponents_are_right_associative() {
+    for (code, grouped) in [
+        ("a ** b ** c", "a ** (b ** c)"),
+        ("-a ** b", "-(a ** b)"),
+        ("a * b ** c ** d", "a * (b ** (c ** d))"),
+        ("a ** -b ** c", "a ** -(b ** c)"),
+    ] {
+        assert_eq!(reparse(code), reparse(grouped), "{code}");
+    }
+    assert_ne!(reparse("a ** b ** c"), reparse("(a ** b) ** c"));
+}
+
+#[test]
+fn bitwise_precedence() {
+    for (code, grouped) in [
+        ("a & b == 0", "(a & b) == 0"),
+        ("a == b | c", "a == (b | c)"),
+        ("a | b ^ c & d", "a | (b ^ (c & d))"),
+        ("a & b << c", "a & (b << c)"),
+        ("a << b + c", "a << (b + c)"),
+        ("~a & b", "(~a) & b"),
+        ("a | b && c", "(a | b) && c"),
+    ] {
+        assert_eq!(reparse(code), reparse(grouped), "{code}");
+    }
+}
+
+#[test]
+fn chained_ranges() {
+    for code in [
+        "(0..5)",
+        "..5",
+        "x[1..]",
+        "(0..1)..2",
+        "for i in 0..n + 1 {}",
+    ] {
+        assert_eq!(error_count(code), 0, "{code}");
+    }
+    for (code, first, second) in [
+        ("0..5..6", 1..3, 4..6),
+        ("0..=1..2", 1..4, 5..7),
+        ("..1..2", 0..2, 3..5),
+        ("let r = 1..2..=3;", 9..11, 12..15),
+    ] {
+        match single_error(code) {
+            ParserError::ChainedRange {
+                first: a,
+                second: b,
+            } => {
+                assert_eq!((a.span, b.span), (first.into(), second.into()), "{code}")
+            }
+            error => panic!("{code}: {error:?}"),
+        }
+    }
+}
+
+fn expected_tokens(code: &str) -> Vec<Token> {
+    match parse(code).1.as_slice() {
+        [ParserError::Expected { expected, .. }] => expected.clone(),
+        errors => panic!("expected a single `Expected` error, got {:?}", errors),
+    }
+}
+
+#[test]
+fn expected_tokens_are_aggregated() {
+    assert_eq!(
+        expected_tokens("foo(1 2)"),
+        [Token::Comma, Token::CloseParen]
+    );
+    assert_eq!(
+        expected_tokens("let x = 1 2"),
+        [Token::Semicolon, Token::Eof]
+    );
+    assert_eq!(
+        expected_tokens("{ 1 2 }"),
+        [Token::Semicolon, Token::CloseCurly]
+    );
+    assert_eq!(
+        expected_tokens("match 1 { 1 2 => 3 }"),
+        [Token::DotDot, Token::DotDotEq, Token::If, Token::FatArrow]
+    );
+}
+
+const LOSSLESS_CASES: &[&str] = &[
+    "",
+    "   \n// only a comment",
+    "/// docs\nfn add(a, b) { a + b } // trailing\n",
+    "let (x, [y, ..]) = (1, [2, 3]);\nx  +  y /* nested /* block */ comment */ * 2",
+    "match x { -1 | 0..=3 => 'a, (_, n) if n > 2 => { n }, _ => ((a) => a)(x) }",
+    "'outer: for i in 0.. { while i < 3 { break 'outer } }\n",
+    "let s = r#\"raw \"string\"#; import \"a.shid\"; t.0.1 .foo[1..=2]",
+    // erroneous input keeps every character too
+    "let a = 1 + ;\nlet b = (2, 3;\nlet c = 4 5;",
+    "fn g(a b) { a } } ) $ @",
+    "match x { 1 => , => 4, _ => 5 ",
+    "{ 1 + /* unterminated",
+];
+
+#[test]
+fn syntax_tree_is_lossless() {
+    for code in LOSSLESS_CASES {
+        let root = syntax(code);
+        assert_eq!(root.to_string(), *code);
+        assert_eq!(root.kind(), NodeKind::Program);
+
+        let mut pos = 0;
+        for tok in root.descendant_tokens() {
+            assert_eq!(
+                tok.span().start,
+                pos,
+                "gap before {:?} in {:?}",
+                tok.text(),
+                code
+            );
+            assert_eq!(&code[tok.span().start..tok.span().end], tok.text());
+            pos = tok.span().end;
+        }
+        assert_eq!(pos, code.len());
+    }
+}
+
+#[test]
+fn syntax_tree_structure() {
+    let root = syntax("let x = 1 + 2 * 3; // done");
+    let items: Vec<_> = root.child_nodes().collect();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].kind(), NodeKind::Let);
+
+    let add = items[0]
+        .child_nodes()
+        .find(|n| n.kind() == NodeKind::BinOp)
+        .unwrap();
+    assert_eq!(add.to_string(), "1 + 2 * 3");
+    let operands: Vec<_> = add.child_nodes().map(|n| n.kind()).collect();
+    assert_eq!(operands, [NodeKind::Literal, NodeKind::BinOp]);
+
+    let comment = root.descendant_tokens().pop().unwrap();
+    assert_eq!(comment.kind(), TokenKind::Trivia(Trivia::LineComment));
+    assert_eq!(comment.parent().kind(), NodeKind::Program);
+}
+
+#[test]
+fn skipped_tokens_are_error_nodes() {
+    let root = syntax("let a = 4 5 6; let b = 1;");
+    let kinds: Vec<_> = root.child_nodes().map(|n| n.kind()).collect();
+    assert_eq!(kinds, [NodeKind::Let, NodeKind::Error, NodeKind::Let]);
+    let error = root.child_nodes().nth(1).unwrap();
+    assert_eq!(error.to_string().trim(), "5 6");
+}
+
+fn format(code: &str) -> String {
+    let mut sources = SourceMap::default();
+    let mut parser = parser(code, &mut sources).with_syntax_tree();
+    let program = parser.parse_program();
+    assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+    format_program(&program, code, parser.syntax.as_ref().unwrap())
+}
+
+#[test]
+fn formatting_is_canonical() {
+    let code = r#"
+/// adds things
+fn add(a,b){a+b}
+import "lib.shid";
+let x=[1,2,3];
+for i in 0..3 { x[i]+=1; }
+
+
+let s = r"raw" + "esc\n";
+match add(1, 2) {
+  0x_03 => true,
+  -1 ..= 4 if x > 2 => { 5 }, -5 => 1.5e3,
+  (a, ..) => a
+}
+if a { 1 } else if b { let q = 2; q } else { 3 }
+let long = [aaaaaaaaaaaaaaaaaaaa, bbbbbbbbbbbbbbbbbbbbbb, cccccccccccccccccccccc, dddddddddddddddddd];
+let t = (1,)
+;{ 1 } - 1
+"#;
+    let expected = r#"/// adds things
+fn add(a, b) { a + b }
+import "lib.shid";
+let x = [1, 2, 3];
+for i in 0..3 {
+    x[i] += 1;
+}
+
+let s = r"raw" + "esc\n";
+match add(1, 2) {
+    0x_03 => true,
+    -1..=4 if x > 2 => { 5 },
+    -5 => 1.5e3,
+    (a, ..) => a,
+}
+if a {
+    1
+} else if b {
+    let q = 2;
+    q
+} else {
+    3
+}
+let long = [
+    aaaaaaaaaaaaaaaaaaaa,
+    bbbbbbbbbbbbbbbbbbbbbb,
+    cccccccccccccccccccccc,
+    dddddddddddddddddd,
+];
+let t = (1,);
+{ 1 } - 1
+"#;
+    assert_eq!(format(code), expected);
+    assert_eq!(format(expected), expected);
+}
+
+#[test]
+fn formatting_uses_minimal_parens() {
+    let cases = [
+        (
+            "((a + b)) * (c * d) ** (e ** f) - (-(g))",
+            "(a + b) * (c * d) ** e ** f - -g",
+        ),
+        ("(a * -b) * c + a * -b * c", "a * (-b) * c + a * -b * c"),
+        ("(a && b) || (c == d)", "a && b || c == d"),
+        ("x = (y = (1..=2))", "x = y = 1..=2"),
+        ("(a..b)..(c)", "(a..b)..c"),
+        ("(!a).b + (1).0 + (2.5).x", "(!a).b + (1).0 + 2.5.x"),
+        ("((x) => x) + ((y) => y)", "((x) => x) + (y) => y"),
+        ("(dbg x)(1) + dbg (-x) * 2", "dbg(x)(1) + dbg(-x) * 2"),
+        ("for i in 0..({ 5 }) {}", "for i in 0..({ 5 }) {}"),
+        ("while true { (break) + 1 }", "while true { (break) + 1 }"),
+    ];
+    for (code, expected) in cases {
+        assert_eq!(format(code), format!("{expected}\n"));
+    }
+}
+
+#[test]
+fn formatting_keeps_comments() {
+    let code = "
+        // leading
+        fn f(a, // first
+          b /* second */) {
+            // at start
+
+            let x = 1 + // inside
+                2;
+            foo(1, /* arg */ 2);
+            let y = { // after brace
+                3 };
+            let a = [1, /* one */ 2];
+            let g = (x /* p */) => x;
+            bar(/* a */ 1, 2 /* b */);
+            // at end
+        }
+        let m = match x {
+            // only comment
+        };
+        /* final */
+    ";
+    let formatted = format(code);
+    assert_eq!(
+        formatted,
+        "\
+// leading
+fn f(
+    a, // first
+    b, /* second */
+) {
+    // at start
+
+    let x = 1 + 2; // inside
+    foo(1, /* arg */ 2);
+    let y = {
+        // after brace
+        3
+    };
+    let a = [1, /* one */ 2];
+    let g = (x /* p */) => x;
+    bar(/* a */ 1, 2 /* b */);
+    // at end
+}
+let m = match x {
+    // only comment
+};
+/* final */
+"
+    );
+    assert_eq!(format(&formatted), formatted);
+
+    // a long list still keeps each block comment next to its element
+    assert_eq!(
+        format(&format!(
+            "f({0}, /* b */ {0}, {0} /* c */, {0});",
+            "x".repeat(30)
+        )),
+        format!(
+            "f(\n    {0},\n    /* b */ {0},\n    {0}, /* c */\n    {0},\n)\n",
+            "x".repeat(30)
+        )
+    );
+}
+
+/// a xorshift generator, so random tests are reproducible
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+    fn chance(&mut self, percent: u64) -> bool {
+        self.next() % 100 < percent
+    }
+    fn pick<T: Clone>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len())].clone()
+    }
+}
+
+/// every operator of a kind, found by lexing all of the operator tokens
+fn operators<T>(to_op: fn(Token) -> Option<T>) -> Vec<T> {
+    let mut lexer = Lexer::new(
+        "+ - * ** / % == != < > <= >= && || ! & | ^ ~ << >> = += -= *= /= %= &= |= ^= <<= >>=",
+    );
+    let mut ops = vec![];
+    loop {
+        match lexer.next() {
+            Token::Eof => break ops,
+            tok => ops.extend(to_op(tok)),
+        }
+    }
+}
+
+const NAMES: &[&str] = &["a", "b", "foo", "bar_2", "_x"];
+const LABELS: &[&str] = &["outer", "inner"];
+const STRINGS: &[&str] = &["", "text", "\"quoted\" \\", "\n\t\r\0", "ünï\u{1}"];
+const DOCS: &[&str] = &["", "doc", " indented", "/ slash"];
+const MAX_DEPTH: usize = 4;
+
+fn detached<T>(val: T) -> Spanned<T> {
+    val.spanned(Span::new(0, 0))
+}
+
+/// generates random asts that parse without errors when printed
+struct AstGen {
+    rng: Rng,
+    depth: usize,
+    /// whether `break` and `continue` have a loop to target
+    in_loop: bool,
+    labels: Vec<String>,
+    bin_ops: Vec<BinOp>,
+    logical_ops: Vec<LogicalOp>,
+    unary_ops: Vec<UnaryOp>,
+    assign_ops: Vec<AssignOp>,
+}
+
+impl AstGen {
+    fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng(seed),
+            depth: 0,
+            in_loop: false,
+            labels: vec![],
+            bin_ops: operators(Token::to_bin_op),
+            logical_ops: operators(Token::to_logical_op),
+            unary_ops: operators(Token::to_unary_op),
+            assign_ops: operators(Token::to_assign_op),
+        }
+    }
+
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.depth += 1;
+        let out = f(self);
+        self.depth -= 1;
+        out
+    }
+    fn in_loop<T>(&mut self, label: &Option<Spanned<String>>, f: impl FnOnce(&mut Self) -> T) -> T {
+        let in_loop = std::mem::replace(&mut self.in_loop, true);
+        self.labels.extend(label.as_ref().map(|l| l.val.clone()));
+        let out = f(self);
+        if label.is_some() {
+            self.labels.pop();
+        }
+        self.in_loop = in_loop;
+        out
+    }
+    fn in_fn_body<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let in_loop = std::mem::take(&mut self.in_loop);
+        let labels = std::mem::take(&mut self.labels);
+        let out = f(self);
+        self.in_loop = in_loop;
+        self.labels = labels;
+        out
+    }
+    fn list<T>(&mut self, min: usize, mut f: impl FnMut(&mut Self) -> T) -> Vec<T> {
+        let len = min + self.rng.below(3);
+        (0..len).map(|_| f(self)).collect()
+    }
+
+    fn name(&mut self) -> String {
+        self.rng.pick(NAMES).into()
+    }
+    fn loop_label(&mut self) -> Option<Spanned<String>> {
+        self.rng
+            .chance(30)
+            .then(|| detached(self.rng.pick(LABELS).into()))
+    }
+    fn jump_label(&mut self) -> Option<Spanned<String>> {
+        if self.labels.is_empty() || self.rng.chance(50) {
+            return None;
+        }
+        Some(detached(self.rng.pick(&self.labels)))
+    }
+
+    fn literal(&mut self) -> Expr {
+        match self.rng.below(4) {
+            0 => Expr::IntegerLiteral((self.rng.next() as u128) >> self.rng.below(64)),
+            1 => {
+                let scale = self.rng.pick(&[1.0, 1e-9, 1e20]);
+                Expr::FloatLiteral(self.rng.below(10000) as f64 / 8.0 * scale)
+            }
+            2 => Expr::StringLiteral(self.rng.pick(STRINGS).into()),
+            _ => Expr::BoolLiteral(self.rng.chance(50)),
+        }
+    }
+    /// a literal in a pattern, which may be a negative number
+    fn pattern_literal(&mut self) -> Spanned<Expr> {
+        let lit = self.literal();
+        let number = matches!(lit, Expr::IntegerLiteral(_) | Expr::FloatLiteral(_));
+        if number && self.rng.chance(50) {
+            return detached(Expr::UnaryOp(UnaryOp::Minus, Box::new(detached(lit))));
+        }
+        detached(lit)
+    }
+
+    fn expr(&mut self) -> Spanned<Expr> {
+        if self.depth >= MAX_DEPTH || self.rng.chance(30) {
+            return detached(self.leaf());
+        }
+        detached(self.nested(Self::compound))
+    }
+    fn leaf(&mut self) -> Expr {
+        match self.rng.below(if self.in_loop { 4 } else { 2 }) {
+            0 => self.literal(),
+            1 => Expr::Ident(self.name()),
+            2 => Expr::Continue {
+                label: self.jump_label(),
+            },
+            _ => Expr::Break {
+                label: self.jump_label(),
+                value: None,
+            },
+        }
+    }
+    fn compound(&mut self) -> Expr {
+        let boxed = |g: &mut Self| Box::new(g.expr());
+        match self.rng.below(18) {
+            0 if self.rng.chance(20) => {
+                Expr::Logical(boxed(self), self.rng.pick(&self.logical_ops), boxed(self))
+            }
+            0 => Expr::BinOp(boxed(self), self.rng.pick(&self.bin_ops), boxed(self)),
+            1 => Expr::UnaryOp(self.rng.pick(&self.unary_ops), boxed(self)),
+            2 => Expr::Assign(
+                Box::new(self.place()),
+                self.rng.pick(&self.assign_ops),
+                boxed(self),
+            ),
+            3 => Expr::Block(Box::new(self.block())),
+            4 => Expr::If {
+                branches: self.list(1, |g| (g.expr(), g.block())),
+                else_: self.rng.chance(50).then(|| Box::new(self.block())),
+            },
+            5 => Expr::Match {
+                scrutinee: boxed(self),
+                arms: self.list(0, Self::arm),
+            },
+            6 => {
+                let label = self.loop_label();
+                let cond = boxed(self);
+                let body = self.in_loop(&label, |g| Box::new(g.block()));
+                Expr::While { label, cond, body }
+            }
+            7 => {
+                let label = self.loop_label();
+                let pattern = self.pattern();
+                let iter = boxed(self);
+                let body = self.in_loop(&label, |g| Box::new(g.block()));
+                Expr::For {
+                    label,
+                    pattern,
+                    iter,
+                    body,
+                }
+            }
+            8 if self.in_loop => Expr::Break {
+                label: self.jump_label(),
+                value: Some(boxed(self)),
+            },
+            8 | 9 => Expr::Dbg(boxed(self)),
+            10 => Expr::Lambda {
+                params: Rc::new(self.list(0, Self::pattern)),
+                body: Rc::new(self.in_fn_body(Self::expr)),
+            },
+            11 => Expr::Call(boxed(self), self.list(0, Self::expr)),
+            12 => Expr::Index(boxed(self), boxed(self)),
+            13 => Expr::TupleField(boxed(self), detached(self.rng.below(12))),
+            14 => Expr::Member(boxed(self), detached(self.name())),
+            15 => Expr::Array(self.list(0, Self::expr)),
+            16 => Expr::Tuple(self.list(0, Self::expr)),
+            _ => Expr::Range {
+                start: self.rng.chance(70).then(|| boxed(self)),
+                end: match self.rng.below(3) {
+                    0 => RangeEnd::Open,
+                    1 => RangeEnd::Exclusive(boxed(self)),
+                    _ => RangeEnd::Inclusive(boxed(self)),
+                },
+            },
+        }
+    }
+    /// an expression that can be assigned to
+    fn place(&mut self) -> Spanned<Expr> {
+        let mut place = detached(Expr::Ident(self.name()));
+        for _ in 0..self.rng.below(3) {
+            place = detached(match self.rng.below(3) {
+                0 => Expr::Index(Box::new(place), Box::new(self.expr())),
+                1 => Expr::TupleField(Box::new(place), detached(self.rng.below(3))),
+                _ => Expr::Member(Box::new(place), detached(self.name())),
+            });
+        }
+        place
+    }
+    fn arm(&mut self) -> MatchArm {
+        let pattern = self.pattern();
+        let guard = self.rng.chance(30).then(|| self.expr());
+        MatchArm {
+            pattern,
+            guard,
+            body: self.expr(),
+        }
+    }
+
+    fn block(&mut self) -> Spanned<Block> {
+        let normal = self.list(0, Self::stmt);
+        let ret = self.rng.chance(70).then(|| self.stmt());
+        detached(Block { normal, ret })
+    }
+    fn stmt(&mut self) -> Documented<Spanned<Stmt>> {
+        let docs = match self.rng.chance(10) {
+            true => vec![detached(self.rng.pick(DOCS).into())],
+            false => vec![],
+        };
+        let stmt = match self.rng.below(5) {
+            0 => Stmt::Let(self.pattern(), self.expr()),
+            1 => Stmt::Fn(FnDecl {
+                name: detached(self.name()),
+                params: Rc::new(self.list(0, Self::pattern)),
+                body: self.in_fn_body(|g| Rc::new(g.nested(Self::block))),
+            }),
+            _ => Stmt::Expr(self.expr().val),
+        };
+        Documented {
+            docs,
+            val: detached(stmt),
+        }
+    }
+
+    fn pattern(&mut self) -> Spanned<Pattern> {
+        let compound = self.depth < MAX_DEPTH && self.rng.chance(40);
+        detached(match self.rng.below(if compound { 6 } else { 4 }) {
+            0 => Pattern::Ident(self.name()),
+            1 => Pattern::Wildcard,
+            2 => Pattern::Literal(Box::new(self.pattern_literal())),
+            3 => match self.rng.below(3) {
+                0 => Pattern::Range {
+                    start: Some(Box::new(self.pattern_literal())),
+                    end: match self.rng.chance(50) {
+                        true => RangeEnd::Inclusive(Box::new(self.pattern_literal())),
+                        false => RangeEnd::Exclusive(Box::new(self.pattern_literal())),
+                    },
+                },
+                1 => Pattern::Range {
+                    start: Some(Box::new(self.pattern_literal())),
+                    end: RangeEnd::Open,
+                },
+                // `..b` would be a rest pattern followed by a literal
+                _ => Pattern::Range {
+                    start: None,
+                    end: RangeEnd::Inclusive(Box::new(self.pattern_literal())),
+                },
+            },
+            4 => Pattern::Tuple(self.nested(Self::list_patterns)),
+            _ => Pattern::Array(self.nested(Self::list_patterns)),
+        })
+    }
+    /// the elements of a tuple or array pattern, with at most one rest pattern
+    fn list_patterns(&mut self) -> Vec<Spanned<Pattern>> {
+        let mut elems = self.list(0, Self::pattern);
+        if self.rng.chance(30) {
+            let at = self.rng.below(elems.len() + 1);
+            elems.insert(at, detached(Pattern::Rest));
+        }
+        elems
+    }
+}
+
+/// gives every span the value generated asts have, to compare asts parsed from different sources
+struct DetachSpans;
+
+impl VisitMut for DetachSpans {
+    fn visit_span(&mut self, span: &mut Span) {
+        *span = Span::new(0, 0);
+    }
+}
+
+/// parses a program that should be a single expression
+fn reparse(code: &str) -> Expr {
+    let (mut program, errors) = parse(code);
+    assert!(errors.is_empty(), "{:?} in\n{}", errors, code);
+    assert_eq!(program.items.len(), 1, "in\n{}", code);
+    match program.items.pop().unwrap().val.val {
+        Item::Stmt(Stmt::Expr(mut expr)) => {
+            DetachSpans.visit_expr(&mut expr, Span::new(0, 0));
+            expr
+        }
+        item => panic!("{:?} in\n{}", item, code),
+    }
+}
+
+#[test]
+fn printing_round_trips() {
+    let mut gen = AstGen::new(0x5eed_1234_abcd);
+    for _ in 0..1000 {
+        let expr = gen.expr();
+        let code = expr.to_string();
+        assert_eq!(reparse(&code), expr.val, "in\n{}", code);
+
+        let block = gen.block();
+        let code = block.to_string();
+        assert_eq!(reparse(&code), Expr::Block(Box::new(block)), "in\n{}", code);
+    }
+}
+
+#[test]
+fn visitors_reach_nested_expressions() {
+    struct Idents(Vec<String>);
+    impl Visit for Idents {
+        fn visit_expr(&mut self, expr: &Expr, _span: Span) {
+            if let Expr::Ident(name) = expr {
+                self.0.push(name.clone());
+            }
+            visit::walk_expr(self, expr);
+        }
+    }
+
+    let (program, _) = parse(
+        "
+        fn f(a) {
+            let (x, ..) = a;
+            'l: for i in x { if i.0 { break 'l dbg -i } }
+        }
+        f([1, 2][0].y, (3,), ..=4)
+        ",
+    );
+    let mut idents = Idents(vec![]);
+    idents.visit_program(&program);
+    assert_eq!(idents.0, ["a", "x", "i", "i", "f"]);
+
+    struct Rename;
+    impl Fold for Rename {
+        fn fold_expr(&mut self, expr: Expr, _span: Span) -> Expr {
+            match expr {
+                Expr::Ident(name) if name == "x" => Expr::Ident("y".into()),
+                expr => fold::walk_expr(self, expr),
+            }
+        }
+    }
+    let renamed = Rename.fold_expr(reparse("x + f(x, (x) => x)"), Span::new(0, 0));
+    assert_eq!(renamed.to_string(), "y + f(y, (x) => y)");
+}
+
+/// run with `cargo test --release -- --ignored --nocapture parse_throughput`
+#[test]
+#[ignore]
+fn parse_throughput() {
+    let mut code = String::new();
+    let mut i = 0;
+    while code.len() < 8 * 1024 * 1024 {
+        code += &format!(
+            "/// item {i}\n\
+             fn f{i}(a, (b, _), [c, ..]) {{\n    \
+                 let x = a * {i} + b ** 2 - c[0..3].len; // comment\n    \
+                 for j in 0..x {{ if j % 2 == 0 {{ continue }} else {{ x += j << 1; }} }}\n    \
+                 match (x, \"str\\n\") {{ (0, _) => 1.5e3, (n, s) if n > 3 => n, _ => ((x) => x)(a) }}\n\
+             }}\n"
+        );
+        i += 1;
+    }
+
+    let mb = code.len() as f64 / 1e6;
+
+    let start = std::time::Instant::now();
+    let lexer = Lexer::new(&code);
+    let lexed = start.elapsed();
+    drop(lexer);
+
+    let start = std::time::Instant::now();
+    let (program, errors) = parse(&code);
+    let parsed = start.elapsed();
+
+    assert!(errors.is_empty(), "{:?}", errors.first());
+    assert_eq!(program.items.len(), i);
+    println!(
+        "lexed {:.1} MB in {:?} ({:.1} MB/s)",
+        mb,
+        lexed,
+        mb / lexed.as_secs_f64()
+    );
+    println!(
+        "parsed {:.1} MB in {:?} ({:.1} MB/s)",
+        mb,
+        parsed,
+        mb / parsed.as_secs_f64()
+    );
+}
@@ -0,0 +1,261 @@
+/// defines a visitor trait with a `walk_*` function for each of its methods, taking the ast by
+/// reference, or by mutable reference when given `mut`. `$shared` is the `Rc` method getting
+/// at the nodes that are shared, as `as_ref` or `make_mut`.
+///
+/// the walks match every variant without wildcards, so a new variant fails to compile until
+/// it's walked
+macro_rules! visitor {
+    ($Visit:ident, $shared:ident $(, $mut:tt)?) => {
+        use std::rc::Rc;
+
+        use crate::{
+            parser::ast::{
+                Block, Documented, Expr, FnDecl, Item, MatchArm, Pattern, Program, RangeEnd, Stmt,
+            },
+            sources::span::{Span, Spanned},
+        };
+
+        /// walks the ast, where each method defaults to walking into the children of the node.
+        /// the span of every `Spanned` is visited before what it wraps
+        // only the tests walk the ast so far
+        #[allow(dead_code)]
+        pub trait $Visit {
+            fn visit_program(&mut self, program: &$($mut)? Program) {
+                walk_program(self, program)
+            }
+            fn visit_item(&mut self, item: &$($mut)? Item, span: Span) {
+                walk_item(self, item, span)
+            }
+            fn visit_fn_decl(&mut self, decl: &$($mut)? FnDecl) {
+                walk_fn_decl(self, decl)
+            }
+            fn visit_stmt(&mut self, stmt: &$($mut)? Stmt, span: Span) {
+                walk_stmt(self, stmt, span)
+            }
+            fn visit_block(&mut self, block: &$($mut)? Block, _span: Span) {
+                walk_block(self, block)
+            }
+            fn visit_expr(&mut self, expr: &$($mut)? Expr, _span: Span) {
+                walk_expr(self, expr)
+            }
+            fn visit_arm(&mut self, arm: &$($mut)? MatchArm) {
+                walk_arm(self, arm)
+            }
+            fn visit_pattern(&mut self, pattern: &$($mut)? Pattern, _span: Span) {
+                walk_pattern(self, pattern)
+            }
+            fn visit_span(&mut self, _span: &$($mut)? Span) {}
+        }
+
+        fn spanned_expr<V: $Visit + ?Sized>(v: &mut V, expr: &$($mut)? Spanned<Expr>) {
+            v.visit_span(&$($mut)? expr.span);
+            v.visit_expr(&$($mut)? expr.val, expr.span);
+        }
+        fn spanned_block<V: $Visit + ?Sized>(v: &mut V, block: &$($mut)? Spanned<Block>) {
+            v.visit_span(&$($mut)? block.span);
+            v.visit_block(&$($mut)? block.val, block.span);
+        }
+        fn spanned_pattern<V: $Visit + ?Sized>(v: &mut V, pattern: &$($mut)? Spanned<Pattern>) {
+            v.visit_span(&$($mut)? pattern.span);
+            v.visit_pattern(&$($mut)? pattern.val, pattern.span);
+        }
+        fn docs<V: $Visit + ?Sized, T>(v: &mut V, documented: &$($mut)? Documented<T>) {
+            for doc in &$($mut)? documented.docs {
+                v.visit_span(&$($mut)? doc.span);
+            }
+        }
+        fn documented_stmt<V: $Visit + ?Sized>(
+            v: &mut V,
+            stmt: &$($mut)? Documented<Spanned<Stmt>>,
+        ) {
+            docs(v, stmt);
+            v.visit_span(&$($mut)? stmt.val.span);
+            v.visit_stmt(&$($mut)? stmt.val.val, stmt.val.span);
+        }
+        fn range_end<V: $Visit + ?Sized>(v: &mut V, end: &$($mut)? RangeEnd) {
+            match end {
+                RangeEnd::Open => {}
+                RangeEnd::Exclusive(end) | RangeEnd::Inclusive(end) => spanned_expr(v, end),
+            }
+        }
+        fn label<V: $Visit + ?Sized>(v: &mut V, label: &$($mut)? Option<Spanned<String>>) {
+            if let Some(label) = label {
+                v.visit_span(&$($mut)? label.span);
+            }
+        }
+
+        pub fn walk_program<V: $Visit + ?Sized>(v: &mut V, program: &$($mut)? Program) {
+            let Program { items } = program;
+            for item in items {
+                docs(v, item);
+                v.visit_span(&$($mut)? item.val.span);
+                v.visit_item(&$($mut)? item.val.val, item.val.span);
+            }
+        }
+        pub fn walk_item<V: $Visit + ?Sized>(v: &mut V, item: &$($mut)? Item, span: Span) {
+            match item {
+                Item::Stmt(stmt) => v.visit_stmt(stmt, span),
+                Item::Import(path) => v.visit_span(&$($mut)? path.span),
+            }
+        }
+        pub fn walk_fn_decl<V: $Visit + ?Sized>(v: &mut V, decl: &$($mut)? FnDecl) {
+            let FnDecl { name, params, body } = decl;
+            v.visit_span(&$($mut)? name.span);
+            for param in Rc::$shared(params) {
+                spanned_pattern(v, param);
+            }
+            spanned_block(v, Rc::$shared(body));
+        }
+        /// `span` is that of the statement, which is also the span of a statement expression
+        pub fn walk_stmt<V: $Visit + ?Sized>(v: &mut V, stmt: &$($mut)? Stmt, span: Span) {
+            match stmt {
+                Stmt::Expr(expr) => v.visit_expr(expr, span),
+                Stmt::Let(pattern, value) => {
+                    spanned_pattern(v, pattern);
+                    spanned_expr(v, value);
+                }
+                Stmt::Fn(decl) => v.visit_fn_decl(decl),
+            }
+        }
+        pub fn walk_block<V: $Visit + ?Sized>(v: &mut V, block: &$($mut)? Block) {
+            let Block { normal, ret } = block;
+            for stmt in normal {
+                documented_stmt(v, stmt);
+            }
+            if let Some(stmt) = ret {
+                documented_stmt(v, stmt);
+            }
+        }
+        pub fn walk_expr<V: $Visit + ?Sized>(v: &mut V, expr: &$($mut)? Expr) {
+            match expr {
+                Expr::IntegerLiteral(_)
+                | Expr::FloatLiteral(_)
+                | Expr::StringLiteral(_)
+                | Expr::BoolLiteral(_)
+                | Expr::Ident(_)
+                | Expr::Error => {}
+
+                Expr::BinOp(left, _, right)
+                | Expr::Logical(left, _, right)
+                | Expr::Assign(left, _, right)
+                | Expr::Index(left, right) => {
+                    spanned_expr(v, left);
+                    spanned_expr(v, right);
+                }
+                Expr::UnaryOp(_, operand) | Expr::Dbg(operand) => spanned_expr(v, operand),
+
+                Expr::Block(block) => spanned_block(v, block),
+                Expr::If { branches, else_ } => {
+                    for (cond, body) in branches {
+                        spanned_expr(v, cond);
+                        spanned_block(v, body);
+                    }
+                    if let Some(else_) = else_ {
+                        spanned_block(v, else_);
+                    }
+                }
+                Expr::Match { scrutinee, arms } => {
+                    spanned_expr(v, scrutinee);
+                    for arm in arms {
+                        v.visit_arm(arm);
+                    }
+                }
+                Expr::While {
+                    label: loop_label,
+                    cond,
+                    body,
+                } => {
+                    label(v, loop_label);
+                    spanned_expr(v, cond);
+                    spanned_block(v, body);
+                }
+                Expr::For {
+                    label: loop_label,
+                    pattern,
+                    iter,
+                    body,
+                } => {
+                    label(v, loop_label);
+                    spanned_pattern(v, pattern);
+                    spanned_expr(v, iter);
+                    spanned_block(v, body);
+                }
+                Expr::Break {
+                    label: jump_label,
+                    value,
+                } => {
+                    label(v, jump_label);
+                    if let Some(value) = value {
+                        spanned_expr(v, value);
+                    }
+                }
+                Expr::Continue { label: jump_label } => label(v, jump_label),
+
+                Expr::Lambda { params, body } => {
+                    for param in Rc::$shared(params) {
+                        spanned_pattern(v, param);
+                    }
+                    spanned_expr(v, Rc::$shared(body));
+                }
+                Expr::Call(target, args) => {
+                    spanned_expr(v, target);
+                    for arg in args {
+                        spanned_expr(v, arg);
+                    }
+                }
+                Expr::TupleField(target, index) => {
+                    spanned_expr(v, target);
+                    v.visit_span(&$($mut)? index.span);
+                }
+                Expr::Member(target, name) => {
+                    spanned_expr(v, target);
+                    v.visit_span(&$($mut)? name.span);
+                }
+
+                Expr::Array(elems) | Expr::Tuple(elems) => {
+                    for elem in elems {
+                        spanned_expr(v, elem);
+                    }
+                }
+                Expr::Range { start, end } => {
+                    if let Some(start) = start {
+                        spanned_expr(v, start);
+                    }
+                    range_end(v, end);
+                }
+            }
+        }
+        pub fn walk_arm<V: $Visit + ?Sized>(v: &mut V, arm: &$($mut)? MatchArm) {
+            let MatchArm {
+                pattern,
+                guard,
+                body,
+            } = arm;
+            spanned_pattern(v, pattern);
+            if let Some(guard) = guard {
+                spanned_expr(v, guard);
+            }
+            spanned_expr(v, body);
+        }
+        pub fn walk_pattern<V: $Visit + ?Sized>(v: &mut V, pattern: &$($mut)? Pattern) {
+            match pattern {
+                Pattern::Ident(_) | Pattern::Wildcard | Pattern::Rest => {}
+                Pattern::Literal(lit) => spanned_expr(v, lit),
+                Pattern::Range { start, end } => {
+                    if let Some(start) = start {
+                        spanned_expr(v, start);
+                    }
+                    range_end(v, end);
+                }
+                Pattern::Tuple(elems) | Pattern::Array(elems) => {
+                    for elem in elems {
+                        spanned_pattern(v, elem);
+                    }
+                }
+            }
+        }
+    };
+}
+pub(super) use visitor;
+
+visitor!(Visit, as_ref);
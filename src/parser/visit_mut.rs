@@ -0,0 +1,2 @@
+// the same walks as `visit`, over a mutable ast
+super::visit::visitor!(VisitMut, make_mut, mut);
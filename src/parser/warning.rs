@@ -5,13 +5,12 @@ make_error! {
 
     ParserWarning {
 
-        @title: "Gaga";
+        @title: "`if` without `else` used as the value of a block";
         @msgs: [
-            v => "{}": sog;
+            area => "This evaluates to `()` when none of its conditions hold";
         ];
-        Blibby {
-            v: CodeArea,
-            sog: String,
+        IfWithoutElse {
+            area: CodeArea,
         }
 
     }
@@ -18,6 +18,14 @@ impl Source {
             content,
         }
     }
+
+    /// the 1-based line and column (in characters) of the byte offset `pos`
+    pub fn line_col(&self, pos: usize) -> (usize, usize) {
+        let before = &self.content[..pos];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        (line, before[line_start..].chars().count() + 1)
+    }
 }
 
 new_key_type! { pub struct SourceKey; }